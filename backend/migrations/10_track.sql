@@ -0,0 +1,30 @@
+CREATE TYPE track_kind AS ENUM ('original', 'translation', 'sdh');
+
+-- Creating the table for the subtitle tracks of a movie, language is a BCP 47 tag like "de"
+CREATE TABLE track (
+    id BIGSERIAL PRIMARY KEY,
+    language VARCHAR(35) NOT NULL,
+    kind track_kind NOT NULL,
+    source_file VARCHAR(255),
+    movie_id BIGINT NOT NULL REFERENCES movie(id)
+);
+
+-- Subtitle sentences and sound events belong to a track, screenplay sentences don't
+ALTER TABLE sentence ADD COLUMN track_id BIGINT REFERENCES track(id);
+ALTER TABLE sound_event ADD COLUMN track_id BIGINT REFERENCES track(id);
+
+-- Moving the subtitles imported so far into one track of unknown language per movie
+INSERT INTO track (language, kind, movie_id)
+SELECT DISTINCT 'und', 'original'::track_kind, movie_id FROM (
+    SELECT movie_id FROM sentence WHERE start_ms IS NOT NULL
+    AND id NOT IN (SELECT script_sentence_id FROM script_alignment)
+    UNION SELECT movie_id FROM sound_event
+) AS subtitled ORDER BY movie_id;
+
+UPDATE sentence SET track_id = track.id FROM track
+WHERE sentence.movie_id = track.movie_id AND sentence.start_ms IS NOT NULL
+AND sentence.id NOT IN (SELECT script_sentence_id FROM script_alignment);
+
+UPDATE sound_event SET track_id = track.id FROM track WHERE sound_event.movie_id = track.movie_id;
+
+ALTER TABLE sound_event ALTER COLUMN track_id SET NOT NULL;
@@ -0,0 +1,6 @@
+-- Creating the table linking sentences to their counterparts in another subtitle track
+CREATE TABLE sentence_translation (
+    sentence_id BIGINT NOT NULL REFERENCES sentence(id) ON DELETE CASCADE,
+    translation_id BIGINT NOT NULL REFERENCES sentence(id) ON DELETE CASCADE,
+    PRIMARY KEY (sentence_id, translation_id)
+);
@@ -0,0 +1,2 @@
+-- Marking characters created for diarized speakers of a transcript until they are merged
+ALTER TABLE character ADD COLUMN placeholder BOOLEAN NOT NULL DEFAULT false;
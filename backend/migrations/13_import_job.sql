@@ -0,0 +1,22 @@
+CREATE TYPE job_status AS ENUM ('queued', 'running', 'succeeded', 'failed');
+
+-- Creating the table for subtitle imports running in the background
+CREATE TABLE import_job (
+    id BIGSERIAL PRIMARY KEY,
+    file_name VARCHAR(255) NOT NULL,
+    status job_status NOT NULL DEFAULT 'queued',
+    processed BIGINT NOT NULL DEFAULT 0,
+    total BIGINT NOT NULL DEFAULT 0,
+    error TEXT,
+    movie_id BIGINT NOT NULL REFERENCES movie(id),
+    track_id BIGINT REFERENCES track(id) ON DELETE SET NULL
+);
+
+-- Creating the table for the problems the parser found in a job's file
+CREATE TABLE import_job_issue (
+    id BIGSERIAL PRIMARY KEY,
+    job_id BIGINT NOT NULL REFERENCES import_job(id),
+    line BIGINT NOT NULL,
+    message TEXT NOT NULL,
+    error BOOLEAN NOT NULL
+);
@@ -0,0 +1,4 @@
+-- The ids rows had in the corpus they were imported from, written back on export
+ALTER TABLE movie ADD COLUMN source_id VARCHAR(255);
+ALTER TABLE character ADD COLUMN source_id VARCHAR(255);
+ALTER TABLE sentence ADD COLUMN source_id VARCHAR(255);
@@ -0,0 +1,4 @@
+-- Sentence times were stored in whole seconds, store them in milliseconds instead
+ALTER TABLE sentence RENAME COLUMN start_time TO start_ms;
+ALTER TABLE sentence RENAME COLUMN end_time TO end_ms;
+UPDATE sentence SET start_ms = start_ms * 1000, end_ms = end_ms * 1000;
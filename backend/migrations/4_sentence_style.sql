@@ -0,0 +1,2 @@
+-- The style name a sentence had in the subtitle file, for example from ASS files
+ALTER TABLE sentence ADD COLUMN style VARCHAR(255);
@@ -0,0 +1,6 @@
+-- Other names a character goes by, used to match speakers on import
+CREATE TABLE character_alias (
+    character_id BIGINT NOT NULL REFERENCES character(id),
+    alias VARCHAR(255) NOT NULL,
+    PRIMARY KEY(character_id, alias)
+);
@@ -0,0 +1,9 @@
+-- Creating the table for non-dialogue annotations like [door slams] or (laughs)
+CREATE TABLE sound_event (
+    id BIGSERIAL PRIMARY KEY,
+    text TEXT NOT NULL,
+    start_ms BIGINT NOT NULL,
+    end_ms BIGINT NOT NULL,
+    position BIGINT NOT NULL,
+    movie_id BIGINT NOT NULL REFERENCES movie(id)
+);
@@ -0,0 +1,16 @@
+CREATE TYPE markup_kind AS ENUM ('italic', 'bold', 'underline', 'color', 'position');
+
+-- Creating the table for formatting spans on a sentence's text, offsets are in characters
+CREATE TABLE sentence_markup (
+    id BIGSERIAL PRIMARY KEY,
+    sentence_id BIGINT NOT NULL REFERENCES sentence(id) ON DELETE CASCADE,
+    kind markup_kind NOT NULL,
+    start_char BIGINT NOT NULL,
+    end_char BIGINT NOT NULL,
+    value VARCHAR(255)
+);
+
+-- Deleting who a sentence is directed to together with the sentence, like its markup
+ALTER TABLE sentence_directed_to DROP CONSTRAINT sentence_directed_to_sentence_id_fkey;
+ALTER TABLE sentence_directed_to ADD CONSTRAINT sentence_directed_to_sentence_id_fkey
+    FOREIGN KEY (sentence_id) REFERENCES sentence(id) ON DELETE CASCADE;
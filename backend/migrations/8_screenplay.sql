@@ -0,0 +1,7 @@
+-- Sentences from a screenplay have no timing until they are aligned with subtitles
+ALTER TABLE sentence ALTER COLUMN start_ms DROP NOT NULL;
+ALTER TABLE sentence ALTER COLUMN end_ms DROP NOT NULL;
+
+-- The scene a sentence is spoken in and how it is spoken, like "whispering"
+ALTER TABLE sentence ADD COLUMN scene_id BIGINT REFERENCES scene(id);
+ALTER TABLE sentence ADD COLUMN parenthetical TEXT;
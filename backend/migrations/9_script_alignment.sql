@@ -0,0 +1,10 @@
+-- Creating the table for proposed matches between screenplay and subtitle sentences
+CREATE TABLE script_alignment (
+    id BIGSERIAL PRIMARY KEY,
+    script_sentence_id BIGINT NOT NULL REFERENCES sentence(id) ON DELETE CASCADE,
+    subtitle_sentence_id BIGINT NOT NULL REFERENCES sentence(id) ON DELETE CASCADE,
+    confidence DOUBLE PRECISION NOT NULL,
+    confirmed BOOLEAN NOT NULL DEFAULT false,
+    applied BOOLEAN NOT NULL DEFAULT false,
+    movie_id BIGINT NOT NULL REFERENCES movie(id)
+);
@@ -0,0 +1,44 @@
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+};
+
+pub mod reimport;
+pub mod script;
+pub mod translation;
+
+/// A timed line of text, like a sentence or a cue of a subtitle file.
+#[derive(Debug, Clone, Copy)]
+pub struct Timed<'a> {
+    pub start: i64,
+    pub end: i64,
+    pub text: &'a str,
+}
+
+/// The share of the shorter line that overlaps the other one in time, from 0 to 1.
+pub fn overlap(a: &Timed, b: &Timed) -> f64 {
+    let shared = a.end.min(b.end) - a.start.max(b.start);
+    let shorter = (a.end - a.start).min(b.end - b.start).max(1);
+    (shared.max(0) as f64 / shorter as f64).min(1.0)
+}
+
+/// The words of a text, lower case and without punctuation, hashed for fast comparison.
+pub fn words(text: &str) -> Vec<u64> {
+    text.split(|c: char| !c.is_alphanumeric() && c != '\'')
+        .map(|word| word.trim_matches('\'').to_lowercase())
+        .filter(|word| !word.is_empty())
+        .map(|word| {
+            let mut hasher = DefaultHasher::new();
+            word.hash(&mut hasher);
+            hasher.finish()
+        })
+        .collect()
+}
+
+/// Like [`words`], but sorted and without duplicates.
+pub fn sorted_words(text: &str) -> Vec<u64> {
+    let mut words = words(text);
+    words.sort_unstable();
+    words.dedup();
+    words
+}
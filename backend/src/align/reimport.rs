@@ -0,0 +1,146 @@
+use super::{overlap, sorted_words, Timed};
+
+// how far a line may have moved and still be matched by its text alone
+const MAX_SHIFT_MS: i64 = 10_000;
+
+/// Pairs the lines of an old subtitle version with the ones of a new version.
+///
+/// Lines are paired in order, if they overlap in time or have a similar text and have
+/// moved less than ten seconds. Returns pairs of indices into `old` and `new`, lines
+/// without a pair were removed or added.
+pub fn diff(old: &[Timed], new: &[Timed]) -> Vec<(usize, usize)> {
+    let old_words: Vec<Vec<u64>> = old.iter().map(|line| sorted_words(line.text)).collect();
+    let new_words: Vec<Vec<u64>> = new.iter().map(|line| sorted_words(line.text)).collect();
+    let (rows, columns) = (old.len() + 1, new.len() + 1);
+    let cell = |i: usize, j: usize| i * columns + j;
+
+    let mut best = vec![0.0_f64; rows * columns];
+    for i in 1..rows {
+        for j in 1..columns {
+            let score = score(
+                &old[i - 1],
+                &new[j - 1],
+                &old_words[i - 1],
+                &new_words[j - 1],
+            );
+            let paired = if score > 0.0 {
+                best[cell(i - 1, j - 1)] + score
+            } else {
+                0.0
+            };
+            best[cell(i, j)] = paired.max(best[cell(i - 1, j)]).max(best[cell(i, j - 1)]);
+        }
+    }
+
+    let mut pairs = Vec::new();
+    let (mut i, mut j) = (rows - 1, columns - 1);
+    while i > 0 && j > 0 {
+        if best[cell(i, j)] == best[cell(i - 1, j)] {
+            i -= 1;
+        } else if best[cell(i, j)] == best[cell(i, j - 1)] {
+            j -= 1;
+        } else {
+            pairs.push((i - 1, j - 1));
+            i -= 1;
+            j -= 1;
+        }
+    }
+    pairs.reverse();
+    pairs
+}
+
+// text similarity plus time overlap, zero for lines that can't be the same
+fn score(old: &Timed, new: &Timed, old_words: &[u64], new_words: &[u64]) -> f64 {
+    let text = dice(old_words, new_words);
+    let overlap = overlap(old, new);
+    let near = (old.start - new.start).abs() <= MAX_SHIFT_MS;
+    if overlap >= 0.3 || near && text >= 0.6 {
+        text + overlap
+    } else {
+        0.0
+    }
+}
+
+// how much two sorted word lists have in common, from 0 to 1
+fn dice(a: &[u64], b: &[u64]) -> f64 {
+    if a.is_empty() && b.is_empty() {
+        return 1.0;
+    }
+    let common = a
+        .iter()
+        .filter(|word| b.binary_search(word).is_ok())
+        .count();
+    2.0 * common as f64 / (a.len() + b.len()) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines<'a>(shift: i64, texts: &[&'a str]) -> Vec<Timed<'a>> {
+        texts
+            .iter()
+            .enumerate()
+            .map(|(i, text)| Timed {
+                start: i as i64 * 3000 + shift,
+                end: i as i64 * 3000 + 2000 + shift,
+                text,
+            })
+            .collect()
+    }
+
+    const TEXTS: [&str; 3] = [
+        "Can we make this quick?",
+        "Well, I thought we'd start with pronunciation.",
+        "Not the hacking and gagging part.",
+    ];
+
+    #[test]
+    fn pairs_unchanged_lines() {
+        let old = lines(0, &TEXTS);
+        assert_eq!(diff(&old, &old), vec![(0, 0), (1, 1), (2, 2)]);
+    }
+
+    #[test]
+    fn pairs_retimed_lines_within_the_maximum_shift() {
+        let old = lines(0, &TEXTS);
+        assert_eq!(
+            diff(&old, &lines(9000, &TEXTS)),
+            vec![(0, 0), (1, 1), (2, 2)]
+        );
+        // moved further and no longer overlapping, they count as removed and added
+        assert_eq!(diff(&old, &lines(MAX_SHIFT_MS + 3000, &TEXTS)), vec![]);
+    }
+
+    #[test]
+    fn pairs_edited_lines_that_overlap() {
+        let old = lines(0, &TEXTS);
+        let new = lines(
+            0,
+            &[
+                "Can we make this quick?",
+                "I thought we would start with how to say things.",
+                "Not the hacking part, please.",
+            ],
+        );
+        assert_eq!(diff(&old, &new), vec![(0, 0), (1, 1), (2, 2)]);
+    }
+
+    #[test]
+    fn leaves_added_and_removed_lines_unpaired() {
+        let old = lines(0, &TEXTS);
+        let mut new = old.clone();
+        new.insert(
+            1,
+            Timed {
+                start: 2100,
+                end: 2900,
+                text: "Sure.",
+            },
+        );
+        assert_eq!(diff(&old, &new), vec![(0, 0), (1, 2), (2, 3)]);
+
+        let new = vec![old[0], old[2]];
+        assert_eq!(diff(&old, &new), vec![(0, 0), (2, 1)]);
+    }
+}
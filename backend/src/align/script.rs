@@ -0,0 +1,185 @@
+use super::{sorted_words, words};
+
+/// A subtitle line matched to a screenplay line.
+#[derive(Debug, Clone, Copy)]
+pub struct Match {
+    /// Index into the screenplay lines.
+    pub script: usize,
+    /// Index into the subtitle lines.
+    pub subtitle: usize,
+    /// How much of the subtitle's words are found in the screenplay line, from 0 to 1.
+    pub confidence: f64,
+}
+
+// what the best path through a cell of the alignment table came from
+#[derive(Clone, Copy, Default)]
+enum Step {
+    #[default]
+    SkipScript,
+    SkipSubtitle,
+    // the script line of this cell is matched to the subtitle of this cell
+    Matched,
+    // a new match after the previous cell on the diagonal
+    Diagonal,
+    // the same script line as in the cell to the left, as subtitles often split a speech
+    Continued,
+}
+
+/// Matches subtitle lines to screenplay lines by their text, keeping both in order.
+///
+/// Subtitles usually split long speeches over several cues and leave lines out, so one
+/// screenplay line can get several consecutive subtitles and lines on both sides can stay
+/// unmatched. Pairs below `min_confidence` are never matched.
+pub fn align(script: &[&str], subtitles: &[&str], min_confidence: f64) -> Vec<Match> {
+    let script: Vec<Vec<u64>> = script.iter().map(|line| sorted_words(line)).collect();
+    let subtitles: Vec<Vec<u64>> = subtitles.iter().map(|line| words(line)).collect();
+    let (rows, columns) = (script.len() + 1, subtitles.len() + 1);
+    let cell = |i: usize, j: usize| i * columns + j;
+
+    // best: best score for the first i script and j subtitle lines
+    // ending: best score where script line i - 1 is matched to subtitle j - 1
+    let mut best = vec![0.0_f64; rows * columns];
+    let mut ending = vec![f64::NEG_INFINITY; rows * columns];
+    let mut best_step = vec![Step::default(); rows * columns];
+    let mut ending_step = vec![Step::default(); rows * columns];
+
+    for i in 0..rows {
+        for j in 0..columns {
+            if i > 0 && j > 0 {
+                let confidence = containment(&script[i - 1], &subtitles[j - 1]);
+                if confidence >= min_confidence {
+                    let diagonal = best[cell(i - 1, j - 1)];
+                    let continued = ending[cell(i, j - 1)];
+                    let (previous, step) = if continued > diagonal {
+                        (continued, Step::Continued)
+                    } else {
+                        (diagonal, Step::Diagonal)
+                    };
+                    ending[cell(i, j)] = previous + confidence;
+                    ending_step[cell(i, j)] = step;
+                }
+            }
+
+            let mut score = 0.0;
+            let mut step = Step::SkipScript;
+            if i > 0 && best[cell(i - 1, j)] > score {
+                score = best[cell(i - 1, j)];
+            }
+            if j > 0 && best[cell(i, j - 1)] > score {
+                score = best[cell(i, j - 1)];
+                step = Step::SkipSubtitle;
+            }
+            if ending[cell(i, j)] > score {
+                score = ending[cell(i, j)];
+                step = Step::Matched;
+            }
+            best[cell(i, j)] = score;
+            best_step[cell(i, j)] = step;
+        }
+    }
+
+    let mut matches = Vec::new();
+    let (mut i, mut j) = (rows - 1, columns - 1);
+    let mut in_ending = false;
+    while i > 0 && j > 0 {
+        if in_ending {
+            matches.push(Match {
+                script: i - 1,
+                subtitle: j - 1,
+                confidence: containment(&script[i - 1], &subtitles[j - 1]),
+            });
+            match ending_step[cell(i, j)] {
+                Step::Continued => j -= 1,
+                _ => {
+                    in_ending = false;
+                    i -= 1;
+                    j -= 1;
+                }
+            }
+            continue;
+        }
+        match best_step[cell(i, j)] {
+            Step::SkipScript => i -= 1,
+            Step::SkipSubtitle => j -= 1,
+            _ => in_ending = true,
+        }
+    }
+    matches.reverse();
+    matches
+}
+
+// the share of the subtitle's words that are in the script line
+fn containment(script: &[u64], subtitle: &[u64]) -> f64 {
+    if subtitle.is_empty() {
+        return 0.0;
+    }
+    let found = subtitle
+        .iter()
+        .filter(|word| script.binary_search(word).is_ok())
+        .count();
+    found as f64 / subtitle.len() as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pairs(matches: &[Match]) -> Vec<(usize, usize)> {
+        matches
+            .iter()
+            .map(|aligned| (aligned.script, aligned.subtitle))
+            .collect()
+    }
+
+    #[test]
+    fn matches_lines_one_to_one() {
+        let script = ["Can we make this quick?", "Let's go."];
+        let subtitles = ["Can we make this quick?", "Let's go!"];
+        let matches = align(&script, &subtitles, 0.5);
+        assert_eq!(pairs(&matches), vec![(0, 0), (1, 1)]);
+        assert!(matches.iter().all(|aligned| aligned.confidence == 1.0));
+    }
+
+    #[test]
+    fn matches_a_speech_split_over_several_subtitles() {
+        let script = [
+            "Well, I thought we'd start with pronunciation, if that's okay with you.",
+            "Not the hacking and gagging part.",
+        ];
+        let subtitles = [
+            "Well, I thought we'd start",
+            "with pronunciation,",
+            "if that's okay with you.",
+            "Not the hacking and gagging part.",
+        ];
+        assert_eq!(
+            pairs(&align(&script, &subtitles, 0.5)),
+            vec![(0, 0), (0, 1), (0, 2), (1, 3)]
+        );
+    }
+
+    #[test]
+    fn skips_lines_missing_on_either_side() {
+        let script = [
+            "Can we make this quick?",
+            "Roxanne Korrine and Andrew Barrett are having an incredibly horrendous breakup.",
+            "Let's go.",
+        ];
+        let subtitles = ["Can we make this quick?", "Hey!", "Let's go."];
+        assert_eq!(
+            pairs(&align(&script, &subtitles, 0.5)),
+            vec![(0, 0), (2, 2)]
+        );
+    }
+
+    #[test]
+    fn leaves_pairs_below_the_minimum_confidence() {
+        let script = ["Can we make this quick?"];
+        // two of five words are in the screenplay line
+        let subtitles = ["Could we make it fast?"];
+        let matches = align(&script, &subtitles, 0.3);
+        assert_eq!(pairs(&matches), vec![(0, 0)]);
+        assert_eq!(matches[0].confidence, 0.4);
+        assert!(align(&script, &subtitles, 0.5).is_empty());
+    }
+}
@@ -0,0 +1,105 @@
+use super::{overlap, Timed};
+
+/// Links the lines of two subtitle tracks of the same film that are shown at the same time.
+///
+/// Both tracks must be ordered by start. Lines are linked when they overlap by at least
+/// `min_overlap` of the shorter one, so a long line can be linked to several short ones
+/// in either direction. A line that overlaps nothing that much is still linked to the
+/// line it overlaps most, to cope with slightly different timing. Returns pairs of
+/// indices into `source` and `target`, ordered by both.
+pub fn align(source: &[Timed], target: &[Timed], min_overlap: f64) -> Vec<(usize, usize)> {
+    let mut overlapping = Vec::new();
+    for (i, line) in source.iter().enumerate() {
+        for (j, other) in target.iter().enumerate() {
+            if other.start >= line.end {
+                break;
+            }
+            let overlap = overlap(line, other);
+            if overlap > 0.0 {
+                overlapping.push((i, j, overlap));
+            }
+        }
+    }
+
+    let mut links: Vec<(usize, usize)> = overlapping
+        .iter()
+        .filter(|(_, _, overlap)| *overlap >= min_overlap)
+        .map(|&(i, j, _)| (i, j))
+        .collect();
+
+    let mut source_linked = vec![false; source.len()];
+    let mut target_linked = vec![false; target.len()];
+    for &(i, j) in &links {
+        source_linked[i] = true;
+        target_linked[j] = true;
+    }
+    let unlinked_sources = (0..source.len()).filter(|&i| !source_linked[i]);
+    for i in unlinked_sources {
+        if let Some((i, j, _)) = best(overlapping.iter().filter(|(source, _, _)| *source == i)) {
+            links.push((*i, *j));
+        }
+    }
+    let unlinked_targets = (0..target.len()).filter(|&j| !target_linked[j]);
+    for j in unlinked_targets {
+        if let Some((i, j, _)) = best(overlapping.iter().filter(|(_, target, _)| *target == j)) {
+            links.push((*i, *j));
+        }
+    }
+
+    links.sort_unstable();
+    links.dedup();
+    links
+}
+
+fn best<'a>(
+    candidates: impl Iterator<Item = &'a (usize, usize, f64)>,
+) -> Option<&'a (usize, usize, f64)> {
+    candidates.max_by(|a, b| a.2.total_cmp(&b.2))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(times: &[(i64, i64)]) -> Vec<Timed<'static>> {
+        times
+            .iter()
+            .map(|&(start, end)| Timed {
+                start,
+                end,
+                text: "",
+            })
+            .collect()
+    }
+
+    #[test]
+    fn links_lines_shown_together() {
+        let source = lines(&[(0, 1000), (2000, 3000)]);
+        let target = lines(&[(100, 1100), (1900, 2900)]);
+        assert_eq!(align(&source, &target, 0.5), vec![(0, 0), (1, 1)]);
+    }
+
+    #[test]
+    fn links_one_line_to_several() {
+        // one long line translated as two short ones
+        let source = lines(&[(0, 4000), (5000, 6000)]);
+        let target = lines(&[(0, 2000), (2000, 4000), (5000, 6000)]);
+        assert_eq!(align(&source, &target, 0.5), vec![(0, 0), (0, 1), (1, 2)]);
+    }
+
+    #[test]
+    fn links_several_lines_to_one() {
+        let source = lines(&[(0, 1500), (1500, 3000)]);
+        let target = lines(&[(0, 3000)]);
+        assert_eq!(align(&source, &target, 0.5), vec![(0, 0), (1, 0)]);
+    }
+
+    #[test]
+    fn falls_back_to_the_best_overlap() {
+        // the source overlaps the first target line by only a fifth and the second by a
+        // tenth, still each of the three lines is linked to the line it overlaps most
+        let source = lines(&[(0, 1000)]);
+        let target = lines(&[(800, 1800), (900, 1900), (3000, 4000)]);
+        assert_eq!(align(&source, &target, 0.5), vec![(0, 0), (0, 1)]);
+    }
+}
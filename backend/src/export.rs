@@ -0,0 +1,268 @@
+use std::collections::HashMap;
+
+use axum::{
+    extract::{Path, Query},
+    http::{header, StatusCode},
+    response::IntoResponse,
+    Extension,
+};
+use encoding_rs::{Encoding, UTF_8, WINDOWS_1252};
+use serde::Deserialize;
+use sqlx::{Pool, Postgres};
+
+use crate::parse::{
+    encoding,
+    markup::{Markup, MarkupKind},
+    srt, Sub, SubKind,
+};
+
+#[derive(Debug, Deserialize)]
+pub struct SrtExportParams {
+    /// The track to export, otherwise the first original track of the movie, or its first
+    /// other track if it has none.
+    track_id: Option<i64>,
+    /// Subtitle sentences get their scene when screenplay alignments are applied.
+    scene_id: Option<i64>,
+    conversation_id: Option<i64>,
+    /// Whether lines start with the name of their speaker.
+    #[serde(default)]
+    speakers: bool,
+    /// Either "utf-8", the default, or "windows-1252".
+    encoding: Option<String>,
+}
+
+/// Renders the sentences of a subtitle track of a movie ordered by position as an srt file.
+///
+/// Served at `/movies/:movie_id/subtitles.srt`, the query string can pick the track, limit
+/// the file to a scene or conversation, prefix lines with their speaker and pick the
+/// encoding. Screenplay sentences aren't part of any track and never exported.
+pub async fn srt_handler(
+    Extension(pool): Extension<Pool<Postgres>>,
+    Path(movie_id): Path<i64>,
+    Query(params): Query<SrtExportParams>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let encoding = output_encoding(params.encoding.as_deref())?;
+
+    let movie_exists = sqlx::query_scalar!(
+        "SELECT EXISTS(SELECT 1 FROM movie WHERE id = $1) AS \"exists!\";",
+        movie_id
+    )
+    .fetch_one(&pool)
+    .await
+    .map_err(internal_error)?;
+    if !movie_exists {
+        return Err((
+            StatusCode::NOT_FOUND,
+            format!("Movie {} does not exist", movie_id),
+        ));
+    }
+
+    // positions start over in every track, so only one track can be exported at a time
+    let track_id = sqlx::query_scalar!(
+        "SELECT id FROM track WHERE movie_id = $1 AND ($2::BIGINT IS NULL OR id = $2) \
+        ORDER BY kind = 'original' DESC, kind = 'translation', id LIMIT 1;",
+        movie_id,
+        params.track_id
+    )
+    .fetch_optional(&pool)
+    .await
+    .map_err(internal_error)?;
+    let track_id = match (track_id, params.track_id) {
+        (Some(track_id), _) => track_id,
+        (None, Some(track_id)) => {
+            return Err((
+                StatusCode::NOT_FOUND,
+                format!("Movie {} has no track {}", movie_id, track_id),
+            ))
+        }
+        (None, None) => {
+            return Err((
+                StatusCode::NOT_FOUND,
+                format!("Movie {} has no subtitle track", movie_id),
+            ))
+        }
+    };
+
+    let sentences = sqlx::query!(
+        "SELECT s.id, s.text, s.start_ms AS \"start_ms!\", s.end_ms AS \"end_ms!\", c.name AS \"speaker?\" \
+        FROM sentence s \
+        LEFT JOIN character c ON c.id = s.speaker_id \
+        WHERE s.track_id = $1 AND s.start_ms IS NOT NULL AND s.end_ms IS NOT NULL \
+        AND ($2::BIGINT IS NULL OR s.scene_id = $2) \
+        AND ($3::BIGINT IS NULL OR s.conversation_id = $3) \
+        ORDER BY s.position, s.start_ms, s.id;",
+        track_id,
+        params.scene_id,
+        params.conversation_id
+    )
+    .fetch_all(&pool)
+    .await
+    .map_err(internal_error)?;
+
+    let sentence_ids: Vec<i64> = sentences.iter().map(|sentence| sentence.id).collect();
+    let rows = sqlx::query!(
+        "SELECT sentence_id, kind as \"kind: MarkupKind\", start_char, end_char, value FROM sentence_markup \
+        WHERE sentence_id = ANY($1) ORDER BY start_char, end_char;",
+        &sentence_ids
+    )
+    .fetch_all(&pool)
+    .await
+    .map_err(internal_error)?;
+    let mut markup: HashMap<i64, Vec<Markup>> = HashMap::new();
+    for row in rows {
+        markup.entry(row.sentence_id).or_default().push(Markup {
+            kind: row.kind,
+            start_char: row.start_char,
+            end_char: row.end_char,
+            value: row.value,
+        });
+    }
+
+    let subs: Vec<Sub> = sentences
+        .into_iter()
+        .map(|sentence| Sub {
+            kind: SubKind::Dialogue,
+            index: None,
+            line: 0,
+            start: sentence.start_ms,
+            end: sentence.end_ms,
+            markup: markup.remove(&sentence.id).unwrap_or_default(),
+            text: sentence.text,
+            speaker: sentence.speaker.filter(|_| params.speakers),
+            style: None,
+        })
+        .collect();
+
+    let content_type = format!("application/x-subrip; charset={}", encoding.name());
+    let disposition = format!("attachment; filename=\"movie-{}.srt\"", movie_id);
+    Ok((
+        [
+            (header::CONTENT_TYPE, content_type),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        encoding::encode(&srt::write(&subs), encoding),
+    ))
+}
+
+fn output_encoding(label: Option<&str>) -> Result<&'static Encoding, (StatusCode, String)> {
+    let encoding = match label {
+        Some(label) => Encoding::for_label(label.trim().as_bytes()),
+        None => Some(UTF_8),
+    };
+    match encoding {
+        Some(encoding) if encoding == UTF_8 || encoding == WINDOWS_1252 => Ok(encoding),
+        _ => Err((
+            StatusCode::BAD_REQUEST,
+            format!(
+                "Unsupported encoding: {}, use utf-8 or windows-1252",
+                label.unwrap_or_default()
+            ),
+        )),
+    }
+}
+
+fn internal_error(error: sqlx::Error) -> (StatusCode, String) {
+    (StatusCode::INTERNAL_SERVER_ERROR, error.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use async_graphql::Schema;
+    use axum::response::IntoResponse;
+
+    use super::*;
+    use crate::model::{MutationRoot, QueryRoot, SubscriptionRoot};
+
+    async fn export(pool: &Pool<Postgres>, movie_id: i64, params: SrtExportParams) -> String {
+        let response = srt_handler(Extension(pool.clone()), Path(movie_id), Query(params))
+            .await
+            .expect("export succeeds")
+            .into_response();
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        String::from_utf8(body.to_vec()).unwrap()
+    }
+
+    #[sqlx::test]
+    async fn exports_the_subtitles_of_a_scene(pool: Pool<Postgres>) {
+        let movie_id =
+            sqlx::query_scalar!("INSERT INTO movie (name) VALUES ('Film') RETURNING id;")
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        let track_id = sqlx::query_scalar!(
+            "INSERT INTO track (movie_id, language, kind) VALUES ($1, 'en', 'original') RETURNING id;",
+            movie_id
+        )
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        let scene_id = sqlx::query_scalar!(
+            "INSERT INTO scene (name, movie_id) VALUES ('Hall', $1) RETURNING id;",
+            movie_id
+        )
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        let script_id = sqlx::query_scalar!(
+            "INSERT INTO sentence (movie_id, scene_id, text, position) \
+            VALUES ($1, $2, 'Where were you?', 0) RETURNING id;",
+            movie_id,
+            scene_id
+        )
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        let subtitle_ids = sqlx::query_scalar!(
+            "INSERT INTO sentence (movie_id, track_id, text, start_ms, end_ms, position) \
+            VALUES ($1, $2, 'Before the scene.', 0, 1000, 0), ($1, $2, 'Where were you?', 2000, 3000, 1) \
+            RETURNING id;",
+            movie_id,
+            track_id
+        )
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+        sqlx::query!(
+            "INSERT INTO script_alignment (script_sentence_id, subtitle_sentence_id, confidence, confirmed, movie_id) \
+            VALUES ($1, $2, 1, true, $3);",
+            script_id,
+            subtitle_ids[1],
+            movie_id
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        let schema = Schema::build(
+            QueryRoot::default(),
+            MutationRoot::default(),
+            SubscriptionRoot::default(),
+        )
+        .data(pool.clone())
+        .finish();
+        let response = schema
+            .execute(format!(
+                "mutation {{ applyScriptAlignments(movieId: {}) {{ id }} }}",
+                movie_id
+            ))
+            .await;
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+
+        let srt = export(
+            &pool,
+            movie_id,
+            SrtExportParams {
+                track_id: None,
+                scene_id: Some(scene_id),
+                conversation_id: None,
+                speakers: false,
+                encoding: None,
+            },
+        )
+        .await;
+        assert_eq!(
+            srt,
+            "1\r\n00:00:02,000 --> 00:00:03,000\r\nWhere were you?\r\n"
+        );
+    }
+}
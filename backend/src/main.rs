@@ -1,8 +1,7 @@
 use std::env;
 
-use crate::parse::parse_srt;
-use async_graphql::{http::GraphiQLSource, EmptySubscription, Schema};
-use async_graphql_axum::{GraphQLRequest, GraphQLResponse};
+use async_graphql::{http::GraphiQLSource, Schema};
+use async_graphql_axum::{GraphQLRequest, GraphQLResponse, GraphQLSubscription};
 use axum::{
     extract::Extension,
     response::{self, IntoResponse},
@@ -10,22 +9,32 @@ use axum::{
     Router, Server,
 };
 use dotenvy::dotenv;
-use model::{MutationRoot, QueryRoot};
+use model::{
+    job::{self, JobUpdates},
+    MutationRoot, QueryRoot, SubscriptionRoot,
+};
 use simple_logger::SimpleLogger;
 use sqlx::{migrate, postgres::PgPoolOptions};
 
+pub mod align;
+pub mod export;
 pub mod model;
 pub mod parse;
 
 async fn graphql_handler(
-    schema: Extension<Schema<QueryRoot, MutationRoot, EmptySubscription>>,
+    schema: Extension<Schema<QueryRoot, MutationRoot, SubscriptionRoot>>,
     req: GraphQLRequest,
 ) -> GraphQLResponse {
     schema.execute(req.into_inner()).await.into()
 }
 
 async fn graphiql() -> impl IntoResponse {
-    response::Html(GraphiQLSource::build().endpoint("/graphql").finish())
+    response::Html(
+        GraphiQLSource::build()
+            .endpoint("/graphql")
+            .subscription_endpoint("/ws")
+            .finish(),
+    )
 }
 
 #[tokio::main]
@@ -34,8 +43,6 @@ async fn main() {
 
     SimpleLogger::new().env().init().unwrap();
 
-    parse_srt("resources/wixxer.srt", 1).await;
-
     // let db = create_client().await;
     let db_url = env::var("DATABASE_URL").expect("DATABASE_URL env is not set");
 
@@ -50,12 +57,20 @@ async fn main() {
         .await
         .expect("Failed to run migrations");
 
+    let failed_jobs = job::fail_unfinished(&pool)
+        .await
+        .expect("Failed to clean up import jobs");
+    if failed_jobs > 0 {
+        log::warn!("Marked {} unfinished import jobs as failed", failed_jobs);
+    }
+
     let schema = Schema::build(
         QueryRoot::default(),
         MutationRoot::default(),
-        EmptySubscription,
+        SubscriptionRoot::default(),
     )
-    .data(pool)
+    .data(pool.clone())
+    .data(JobUpdates::new())
     .finish();
 
     // Connect to the server
@@ -63,18 +78,21 @@ async fn main() {
     let app = Router::new()
         .route("/", get(graphiql))
         .route("/graphql", post(graphql_handler))
-        .layer(Extension(schema));
+        .route_service("/ws", GraphQLSubscription::new(schema.clone()))
+        .route("/movies/:movie_id/subtitles.srt", get(export::srt_handler))
+        .layer(Extension(schema))
+        .layer(Extension(pool));
 
     let address = env::var("AXUM_LISTEN_ADDRESS").expect("AXUM_LISTEN_ADDRESS env is not set");
 
-    println!("");
+    println!();
     println!("    ____  _       __                           ");
     println!("   / __ \\(_)___ _/ /___  ____ _____ ____  _____");
     println!("  / / / / / __ `/ / __ \\/ __ `/ __ `/ _ \\/ ___/");
     println!(" / /_/ / / /_/ / / /_/ / /_/ / /_/ /  __/ /    ");
     println!("/_____/_/\\__,_/_/\\____/\\__, /\\__, /\\___/_/     ");
     println!("                      /____//____/             ");
-    println!("");
+    println!();
 
     log::info!("🎸 Starting Axum!");
     log::info!("🛝  Playground at http://{}.", address);
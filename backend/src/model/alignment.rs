@@ -0,0 +1,224 @@
+use async_graphql::*;
+use serde::{Deserialize, Serialize};
+use sqlx::{Pool, Postgres};
+
+use crate::align::script;
+
+use super::sentence::Sentence;
+
+/// A proposed match between a sentence from a screenplay and one from subtitles.
+///
+/// Applying it copies the subtitle's timing onto the screenplay sentence and the
+/// screenplay's speaker and scene onto the subtitle sentence.
+#[derive(Debug, Serialize, Deserialize, SimpleObject)]
+#[graphql(complex)]
+pub struct ScriptAlignment {
+    pub id: i64,
+    /// How much of the subtitle's words are found in the screenplay sentence, from 0 to 1.
+    pub confidence: f64,
+    pub confirmed: bool,
+    pub applied: bool,
+    #[graphql(skip)]
+    pub script_sentence_id: i64,
+    #[graphql(skip)]
+    pub subtitle_sentence_id: i64,
+    #[graphql(skip)]
+    pub movie_id: i64,
+}
+
+// SQLx and async-graphql implementations for ScriptAlignment
+
+#[ComplexObject]
+impl ScriptAlignment {
+    async fn script_sentence<'ctx>(&self, ctx: &Context<'ctx>) -> Result<Sentence, Error> {
+        let pool = ctx.data::<Pool<Postgres>>()?;
+        let sentence: Sentence = sqlx::query_as!(
+            Sentence,
+            "SELECT * FROM sentence WHERE id = $1;",
+            self.script_sentence_id
+        )
+        .fetch_one(pool)
+        .await?;
+        Ok(sentence)
+    }
+
+    async fn subtitle_sentence<'ctx>(&self, ctx: &Context<'ctx>) -> Result<Sentence, Error> {
+        let pool = ctx.data::<Pool<Postgres>>()?;
+        let sentence: Sentence = sqlx::query_as!(
+            Sentence,
+            "SELECT * FROM sentence WHERE id = $1;",
+            self.subtitle_sentence_id
+        )
+        .fetch_one(pool)
+        .await?;
+        Ok(sentence)
+    }
+}
+
+// SQLx and async-graphql implementations for AlignmentQuery
+
+#[derive(Default)]
+pub struct AlignmentQuery;
+
+#[Object]
+impl AlignmentQuery {
+    async fn script_alignments(
+        &self,
+        ctx: &Context<'_>,
+        movie_id: i64,
+        confirmed: Option<bool>,
+    ) -> Result<Vec<ScriptAlignment>, Error> {
+        let pool = ctx.data::<Pool<Postgres>>()?;
+        let alignments: Vec<ScriptAlignment> = sqlx::query_as!(
+            ScriptAlignment,
+            "SELECT a.* FROM script_alignment as a \
+            INNER JOIN sentence as s ON s.id = a.script_sentence_id \
+            WHERE a.movie_id = $1 AND ($2::BOOLEAN IS NULL OR a.confirmed = $2) \
+            ORDER BY s.position, a.id;",
+            movie_id,
+            confirmed
+        )
+        .fetch_all(pool)
+        .await?;
+        Ok(alignments)
+    }
+}
+
+#[derive(Default)]
+pub struct AlignmentMutation;
+
+#[Object]
+impl AlignmentMutation {
+    /// Matches the untimed screenplay sentences of a movie to its timed subtitle sentences
+    /// by their text and stores the matches for review. Unapplied matches of an earlier run
+    /// are replaced.
+    async fn align_script(
+        &self,
+        ctx: &Context<'_>,
+        movie_id: i64,
+        #[graphql(
+            desc = "Subtitle track to align with, all tracks except translations when omitted"
+        )]
+        track_id: Option<i64>,
+        #[graphql(default = 0.5)] min_confidence: f64,
+    ) -> Result<Vec<ScriptAlignment>, Error> {
+        let pool = ctx.data::<Pool<Postgres>>()?;
+        let mut transaction = pool.begin().await?;
+
+        sqlx::query!(
+            "DELETE FROM script_alignment WHERE movie_id = $1 AND NOT applied;",
+            movie_id
+        )
+        .execute(&mut transaction)
+        .await?;
+
+        let script_sentences = sqlx::query_as!(
+            Sentence,
+            "SELECT * FROM sentence WHERE movie_id = $1 AND track_id IS NULL AND start_ms IS NULL \
+            ORDER BY position;",
+            movie_id
+        )
+        .fetch_all(&mut transaction)
+        .await?;
+
+        let subtitle_sentences = sqlx::query_as!(
+            Sentence,
+            "SELECT s.* FROM sentence as s INNER JOIN track as t ON t.id = s.track_id \
+            WHERE s.movie_id = $1 AND (s.track_id = $2 OR $2 IS NULL AND t.kind <> 'translation') \
+            ORDER BY s.start_ms, s.position;",
+            movie_id,
+            track_id
+        )
+        .fetch_all(&mut transaction)
+        .await?;
+
+        let script_texts: Vec<&str> = script_sentences.iter().map(|s| s.text.as_str()).collect();
+        let subtitle_texts: Vec<&str> =
+            subtitle_sentences.iter().map(|s| s.text.as_str()).collect();
+
+        let mut alignments = Vec::new();
+        for aligned in script::align(&script_texts, &subtitle_texts, min_confidence) {
+            let alignment: ScriptAlignment = sqlx::query_as!(
+                ScriptAlignment,
+                "INSERT INTO script_alignment (script_sentence_id, subtitle_sentence_id, confidence, movie_id) VALUES ($1, $2, $3, $4) RETURNING *;",
+                script_sentences[aligned.script].id,
+                subtitle_sentences[aligned.subtitle].id,
+                aligned.confidence,
+                movie_id
+            )
+            .fetch_one(&mut transaction)
+            .await?;
+            alignments.push(alignment);
+        }
+
+        transaction.commit().await?;
+        Ok(alignments)
+    }
+
+    async fn confirm_script_alignments(
+        &self,
+        ctx: &Context<'_>,
+        ids: Vec<i64>,
+        #[graphql(default = true)] confirmed: bool,
+    ) -> Result<Vec<ScriptAlignment>, Error> {
+        let pool = ctx.data::<Pool<Postgres>>()?;
+        let alignments: Vec<ScriptAlignment> = sqlx::query_as!(
+            ScriptAlignment,
+            "UPDATE script_alignment SET confirmed = $1 WHERE id = ANY($2) AND NOT applied RETURNING *;",
+            confirmed,
+            &ids
+        )
+        .fetch_all(pool)
+        .await?;
+        Ok(alignments)
+    }
+
+    /// Applies the confirmed matches of a movie. Screenplay sentences get the timing of
+    /// their subtitles, spanning all of them if a speech was split, and subtitle sentences
+    /// get the scene and, if they have none, the speaker of their screenplay sentence.
+    async fn apply_script_alignments(
+        &self,
+        ctx: &Context<'_>,
+        movie_id: i64,
+    ) -> Result<Vec<ScriptAlignment>, Error> {
+        let pool = ctx.data::<Pool<Postgres>>()?;
+        let mut transaction = pool.begin().await?;
+
+        sqlx::query!(
+            "UPDATE sentence SET start_ms = timing.start_ms, end_ms = timing.end_ms \
+            FROM (SELECT a.script_sentence_id, MIN(s.start_ms) as start_ms, MAX(s.end_ms) as end_ms \
+                FROM script_alignment as a \
+                INNER JOIN sentence as s ON s.id = a.subtitle_sentence_id \
+                WHERE a.movie_id = $1 AND a.confirmed AND NOT a.applied \
+                GROUP BY a.script_sentence_id) as timing \
+            WHERE sentence.id = timing.script_sentence_id;",
+            movie_id
+        )
+        .execute(&mut transaction)
+        .await?;
+
+        sqlx::query!(
+            "UPDATE sentence SET speaker_id = COALESCE(sentence.speaker_id, script.speaker_id), \
+            scene_id = COALESCE(script.scene_id, sentence.scene_id) \
+            FROM script_alignment as a \
+            INNER JOIN sentence as script ON script.id = a.script_sentence_id \
+            WHERE sentence.id = a.subtitle_sentence_id \
+            AND a.movie_id = $1 AND a.confirmed AND NOT a.applied;",
+            movie_id
+        )
+        .execute(&mut transaction)
+        .await?;
+
+        let alignments: Vec<ScriptAlignment> = sqlx::query_as!(
+            ScriptAlignment,
+            "UPDATE script_alignment SET applied = true \
+            WHERE movie_id = $1 AND confirmed AND NOT applied RETURNING *;",
+            movie_id
+        )
+        .fetch_all(&mut transaction)
+        .await?;
+
+        transaction.commit().await?;
+        Ok(alignments)
+    }
+}
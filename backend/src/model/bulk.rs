@@ -0,0 +1,200 @@
+// Batched inserts for imports, one statement per table instead of one per row
+
+use sqlx::{
+    postgres::{PgHasArrayType, PgTypeInfo},
+    Postgres, Transaction,
+};
+
+use crate::parse::markup::{Markup, MarkupKind};
+
+use super::{sentence::Sentence, sound_event::SoundEvent};
+
+/// How many rows go into one statement, imports report their progress in between.
+pub const BATCH_SIZE: usize = 1000;
+
+impl PgHasArrayType for MarkupKind {
+    fn array_type_info() -> PgTypeInfo {
+        PgTypeInfo::with_name("_markup_kind")
+    }
+}
+
+/// A sentence to insert with [`insert_sentences`].
+pub struct NewSentence<'a> {
+    pub movie_id: i64,
+    pub track_id: Option<i64>,
+    pub scene_id: Option<i64>,
+    pub start_ms: Option<i64>,
+    pub end_ms: Option<i64>,
+    pub text: &'a str,
+    pub position: i64,
+    pub speaker_id: Option<i64>,
+    pub conversation_id: Option<i64>,
+    pub style: Option<&'a str>,
+    pub parenthetical: Option<&'a str>,
+    pub source_id: Option<&'a str>,
+    pub markup: &'a [Markup],
+}
+
+/// A sound event to insert with [`insert_sound_events`].
+pub struct NewSoundEvent<'a> {
+    pub movie_id: i64,
+    pub track_id: i64,
+    pub start_ms: i64,
+    pub end_ms: i64,
+    pub text: &'a str,
+    pub position: i64,
+}
+
+/// Inserts sentences and their markup with one statement per table and returns them in
+/// the order they were given, matched by their ordinal in the batch.
+pub async fn insert_sentences(
+    transaction: &mut Transaction<'_, Postgres>,
+    new: &[NewSentence<'_>],
+) -> Result<Vec<Sentence>, sqlx::Error> {
+    let column = |value: fn(&NewSentence) -> Option<i64>| new.iter().map(value).collect::<Vec<_>>();
+    let movie_ids: Vec<i64> = new.iter().map(|sentence| sentence.movie_id).collect();
+    let positions: Vec<i64> = new.iter().map(|sentence| sentence.position).collect();
+    let texts: Vec<&str> = new.iter().map(|sentence| sentence.text).collect();
+    let styles: Vec<Option<&str>> = new.iter().map(|sentence| sentence.style).collect();
+    let parentheticals: Vec<Option<&str>> =
+        new.iter().map(|sentence| sentence.parenthetical).collect();
+    let source_ids: Vec<Option<&str>> = new.iter().map(|sentence| sentence.source_id).collect();
+
+    // ids are taken up front, so every inserted row can be matched with its ordinal
+    let rows = sqlx::query!(
+        "WITH new_sentence AS ( \
+            SELECT nextval('sentence_id_seq') AS id, t.* \
+            FROM UNNEST($1::BIGINT[], $2::BIGINT[], $3::BIGINT[], $4::BIGINT[], $5::BIGINT[], $6::TEXT[], $7::BIGINT[], $8::BIGINT[], $9::BIGINT[], $10::VARCHAR[], $11::TEXT[], $12::VARCHAR[]) \
+            WITH ORDINALITY AS t(movie_id, track_id, scene_id, start_ms, end_ms, text, position, speaker_id, conversation_id, style, parenthetical, source_id, ord) \
+        ), inserted AS ( \
+            INSERT INTO sentence (id, movie_id, track_id, scene_id, start_ms, end_ms, text, position, speaker_id, conversation_id, style, parenthetical, source_id) \
+            SELECT id, movie_id, track_id, scene_id, start_ms, end_ms, text, position, speaker_id, conversation_id, style, parenthetical, source_id FROM new_sentence \
+            RETURNING * \
+        ) \
+        SELECT n.ord AS \"ord!\", s.id AS \"id!\", s.text AS \"text!\", s.start_ms, s.end_ms, s.position AS \"position!\", \
+        s.style, s.parenthetical, s.speaker_id, s.conversation_id, s.scene_id, s.movie_id AS \"movie_id!\", s.track_id, s.source_id \
+        FROM inserted s JOIN new_sentence n ON n.id = s.id ORDER BY n.ord;",
+        &movie_ids,
+        &column(|sentence| sentence.track_id) as _,
+        &column(|sentence| sentence.scene_id) as _,
+        &column(|sentence| sentence.start_ms) as _,
+        &column(|sentence| sentence.end_ms) as _,
+        &texts as _,
+        &positions,
+        &column(|sentence| sentence.speaker_id) as _,
+        &column(|sentence| sentence.conversation_id) as _,
+        &styles as _,
+        &parentheticals as _,
+        &source_ids as _
+    )
+    .fetch_all(&mut *transaction)
+    .await?;
+
+    let mut sentences = Vec::with_capacity(rows.len());
+    let mut markup: Vec<(i64, &Markup)> = Vec::new();
+    for row in rows {
+        // ordinals count from 1
+        let given = &new[row.ord as usize - 1];
+        markup.extend(given.markup.iter().map(|markup| (row.id, markup)));
+        sentences.push(Sentence {
+            id: row.id,
+            text: row.text,
+            start_ms: row.start_ms,
+            end_ms: row.end_ms,
+            position: row.position,
+            style: row.style,
+            parenthetical: row.parenthetical,
+            speaker_id: row.speaker_id,
+            conversation_id: row.conversation_id,
+            scene_id: row.scene_id,
+            movie_id: row.movie_id,
+            track_id: row.track_id,
+            source_id: row.source_id,
+        });
+    }
+
+    insert_markup(transaction, &markup).await?;
+
+    Ok(sentences)
+}
+
+/// Inserts markup of sentences that already exist with one statement.
+pub async fn insert_markup(
+    transaction: &mut Transaction<'_, Postgres>,
+    markup: &[(i64, &Markup)],
+) -> Result<(), sqlx::Error> {
+    if markup.is_empty() {
+        return Ok(());
+    }
+    let sentence_ids: Vec<i64> = markup.iter().map(|(id, _)| *id).collect();
+    let kinds: Vec<MarkupKind> = markup.iter().map(|(_, markup)| markup.kind).collect();
+    let starts: Vec<i64> = markup.iter().map(|(_, markup)| markup.start_char).collect();
+    let ends: Vec<i64> = markup.iter().map(|(_, markup)| markup.end_char).collect();
+    let values: Vec<Option<&str>> = markup
+        .iter()
+        .map(|(_, markup)| markup.value.as_deref())
+        .collect();
+    sqlx::query!(
+        "INSERT INTO sentence_markup (sentence_id, kind, start_char, end_char, value) \
+        SELECT * FROM UNNEST($1::BIGINT[], $2::markup_kind[], $3::BIGINT[], $4::BIGINT[], $5::VARCHAR[]);",
+        &sentence_ids,
+        &kinds as _,
+        &starts,
+        &ends,
+        &values as _
+    )
+    .execute(&mut *transaction)
+    .await?;
+    Ok(())
+}
+
+/// Inserts sound events with one statement and returns them in the order they were given,
+/// matched by their ordinal in the batch.
+pub async fn insert_sound_events(
+    transaction: &mut Transaction<'_, Postgres>,
+    new: &[NewSoundEvent<'_>],
+) -> Result<Vec<SoundEvent>, sqlx::Error> {
+    let movie_ids: Vec<i64> = new.iter().map(|event| event.movie_id).collect();
+    let track_ids: Vec<i64> = new.iter().map(|event| event.track_id).collect();
+    let starts: Vec<i64> = new.iter().map(|event| event.start_ms).collect();
+    let ends: Vec<i64> = new.iter().map(|event| event.end_ms).collect();
+    let texts: Vec<&str> = new.iter().map(|event| event.text).collect();
+    let positions: Vec<i64> = new.iter().map(|event| event.position).collect();
+
+    // ids are taken up front, so every inserted row can be matched with its ordinal
+    let rows = sqlx::query!(
+        "WITH new_sound_event AS ( \
+            SELECT nextval('sound_event_id_seq') AS id, t.* \
+            FROM UNNEST($1::BIGINT[], $2::BIGINT[], $3::BIGINT[], $4::BIGINT[], $5::TEXT[], $6::BIGINT[]) \
+            WITH ORDINALITY AS t(movie_id, track_id, start_ms, end_ms, text, position, ord) \
+        ), inserted AS ( \
+            INSERT INTO sound_event (id, movie_id, track_id, start_ms, end_ms, text, position) \
+            SELECT id, movie_id, track_id, start_ms, end_ms, text, position FROM new_sound_event \
+            RETURNING * \
+        ) \
+        SELECT n.ord AS \"ord!\", e.id AS \"id!\", e.text AS \"text!\", e.start_ms AS \"start_ms!\", e.end_ms AS \"end_ms!\", \
+        e.position AS \"position!\", e.movie_id AS \"movie_id!\", e.track_id AS \"track_id!\" \
+        FROM inserted e JOIN new_sound_event n ON n.id = e.id ORDER BY n.ord;",
+        &movie_ids,
+        &track_ids,
+        &starts,
+        &ends,
+        &texts as _,
+        &positions
+    )
+    .fetch_all(&mut *transaction)
+    .await?;
+    let sound_events = rows
+        .into_iter()
+        .map(|row| SoundEvent {
+            id: row.id,
+            text: row.text,
+            start_ms: row.start_ms,
+            end_ms: row.end_ms,
+            position: row.position,
+            movie_id: row.movie_id,
+            track_id: row.track_id,
+        })
+        .collect();
+    Ok(sound_events)
+}
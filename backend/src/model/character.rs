@@ -13,6 +13,11 @@ pub struct Character {
     pub description: Option<String>,
     #[graphql(skip)]
     pub movie_id: i64,
+    /// Whether the character stands in for a diarized speaker like "SPEAKER_01" and should
+    /// be merged into a real character.
+    pub placeholder: bool,
+    /// The id the character has in the corpus it was imported from, like "u0".
+    pub source_id: Option<String>,
 }
 
 // SQLx and async-graphql implementations for Character
@@ -26,6 +31,18 @@ impl Character {
             .await?;
         Ok(movie)
     }
+
+    /// Other names the character goes by, speakers with these names are matched on import.
+    async fn aliases<'ctx>(&self, ctx: &Context<'ctx>) -> Result<Vec<String>, Error> {
+        let pool = ctx.data::<Pool<Postgres>>()?;
+        let aliases = sqlx::query_scalar!(
+            "SELECT alias FROM character_alias WHERE character_id = $1 ORDER BY alias;",
+            self.id
+        )
+        .fetch_all(pool)
+        .await?;
+        Ok(aliases)
+    }
 }
 
 // SQLx and async-graphql implementations for CharacterQuery
@@ -35,12 +52,18 @@ pub struct CharacterQuery;
 
 #[Object]
 impl CharacterQuery {
-    async fn characters(&self, ctx: &Context<'_>, movie_id: i64) -> Result<Vec<Character>, Error> {
+    async fn characters(
+        &self,
+        ctx: &Context<'_>,
+        movie_id: i64,
+        placeholder: Option<bool>,
+    ) -> Result<Vec<Character>, Error> {
         let pool = ctx.data::<Pool<Postgres>>()?;
         let characters = sqlx::query_as!(
             Character,
-            "SELECT * FROM character WHERE movie_id = $1;",
-            movie_id
+            "SELECT * FROM character WHERE movie_id = $1 AND ($2::BOOLEAN IS NULL OR placeholder = $2);",
+            movie_id,
+            placeholder
         )
         .fetch_all(pool)
         .await?;
@@ -116,4 +139,123 @@ impl CharacterMutation {
         .await?;
         Ok(character)
     }
+
+    /// Merges a character, like a placeholder for a diarized speaker, into another one. The
+    /// lines, addressees, conversations and aliases move over and the character is deleted.
+    async fn merge_characters(
+        &self,
+        ctx: &Context<'_>,
+        id: i64,
+        into_id: i64,
+    ) -> Result<Character, Error> {
+        let pool = ctx.data::<Pool<Postgres>>()?;
+        if id == into_id {
+            return Err(Error::new("A character can't be merged into itself"));
+        }
+        let mut transaction = pool.begin().await?;
+
+        let merged = sqlx::query_as!(Character, "SELECT * FROM character WHERE id = $1;", id)
+            .fetch_one(&mut transaction)
+            .await?;
+        let character =
+            sqlx::query_as!(Character, "SELECT * FROM character WHERE id = $1;", into_id)
+                .fetch_one(&mut transaction)
+                .await?;
+        if merged.movie_id != character.movie_id {
+            return Err(Error::new("Characters of different movies can't be merged"));
+        }
+
+        sqlx::query!(
+            "UPDATE sentence SET speaker_id = $2 WHERE speaker_id = $1;",
+            id,
+            into_id
+        )
+        .execute(&mut transaction)
+        .await?;
+        sqlx::query!(
+            "INSERT INTO sentence_directed_to (sentence_id, directed_to_id) \
+            SELECT sentence_id, $2 FROM sentence_directed_to WHERE directed_to_id = $1 \
+            ON CONFLICT DO NOTHING;",
+            id,
+            into_id
+        )
+        .execute(&mut transaction)
+        .await?;
+        sqlx::query!(
+            "DELETE FROM sentence_directed_to WHERE directed_to_id = $1;",
+            id
+        )
+        .execute(&mut transaction)
+        .await?;
+        sqlx::query!(
+            "INSERT INTO conversation_participants (conversation_id, participant_id) \
+            SELECT conversation_id, $2 FROM conversation_participants WHERE participant_id = $1 \
+            ON CONFLICT DO NOTHING;",
+            id,
+            into_id
+        )
+        .execute(&mut transaction)
+        .await?;
+        sqlx::query!(
+            "DELETE FROM conversation_participants WHERE participant_id = $1;",
+            id
+        )
+        .execute(&mut transaction)
+        .await?;
+
+        // a real name stays around as alias, so later imports find the character by it
+        sqlx::query!(
+            "INSERT INTO character_alias (character_id, alias) \
+            SELECT $2::BIGINT, alias FROM character_alias WHERE character_id = $1 \
+            UNION SELECT $2::BIGINT, name FROM character WHERE id = $1 AND NOT placeholder \
+            ON CONFLICT DO NOTHING;",
+            id,
+            into_id
+        )
+        .execute(&mut transaction)
+        .await?;
+        sqlx::query!("DELETE FROM character_alias WHERE character_id = $1;", id)
+            .execute(&mut transaction)
+            .await?;
+        sqlx::query!("DELETE FROM character WHERE id = $1;", id)
+            .execute(&mut transaction)
+            .await?;
+
+        transaction.commit().await?;
+        Ok(character)
+    }
+
+    async fn add_alias(
+        &self,
+        ctx: &Context<'_>,
+        character_id: i64,
+        alias: String,
+    ) -> Result<bool, Error> {
+        let pool = ctx.data::<Pool<Postgres>>()?;
+        sqlx::query!(
+            "INSERT INTO character_alias (character_id, alias) VALUES ($1, $2) RETURNING *;",
+            character_id,
+            alias.trim()
+        )
+        .fetch_one(pool)
+        .await?;
+        Ok(true)
+    }
+
+    async fn remove_alias(
+        &self,
+        ctx: &Context<'_>,
+        character_id: i64,
+        alias: String,
+    ) -> Result<bool, Error> {
+        let pool = ctx.data::<Pool<Postgres>>()?;
+        sqlx::query!(
+            "DELETE FROM character_alias WHERE character_id = $1 AND alias = $2 RETURNING *;",
+            character_id,
+            alias.trim()
+        )
+        .fetch_one(pool)
+        .await?;
+        Ok(true)
+    }
 }
@@ -0,0 +1,443 @@
+use std::collections::{HashMap, HashSet};
+
+use async_graphql::*;
+use sqlx::{Pool, Postgres};
+
+use crate::parse::{
+    cornell::{
+        self, CornellCharacter, CornellConversation, CornellFiles, CornellLine, CornellMovie,
+    },
+    encoding,
+    labels::title_case,
+    ParseReport,
+};
+
+use super::{
+    bulk::{self, NewSentence, BATCH_SIZE},
+    import::{encoding_for_label, read_upload},
+    movie::Movie,
+};
+
+/// A movie of the Cornell Movie-Dialogs corpus and what was imported for it.
+#[derive(Debug, SimpleObject)]
+pub struct CornellMovieImport {
+    /// The id the movie has in the corpus, like "m0".
+    pub corpus_id: String,
+    pub movie: Movie,
+    pub character_count: usize,
+    pub sentence_count: usize,
+    pub conversation_count: usize,
+}
+
+#[derive(Debug, SimpleObject)]
+pub struct CornellImportResult {
+    pub movies: Vec<CornellMovieImport>,
+    pub report: ParseReport,
+}
+
+/// The four files of the Cornell Movie-Dialogs corpus.
+#[derive(Debug, SimpleObject)]
+pub struct CornellExport {
+    pub movie_titles_metadata: String,
+    pub movie_characters_metadata: String,
+    pub movie_lines: String,
+    pub movie_conversations: String,
+}
+
+#[derive(Default)]
+pub struct CornellQuery;
+
+#[Object]
+impl CornellQuery {
+    /// Writes movies in the format of the Cornell Movie-Dialogs corpus.
+    ///
+    /// Sentences of translation tracks and sentences without a speaker are left out. Movies,
+    /// characters and lines imported from the corpus keep their ids, the others are numbered
+    /// after the highest id in use. A conversation is written with its first two speakers or
+    /// participants, it is left out if it doesn't have two.
+    async fn cornell_export(
+        &self,
+        ctx: &Context<'_>,
+        movie_ids: Vec<i64>,
+    ) -> Result<CornellExport, Error> {
+        let pool = ctx.data::<Pool<Postgres>>()?;
+        let movies: Vec<Movie> = sqlx::query_as!(
+            Movie,
+            "SELECT * FROM movie WHERE id = ANY($1) ORDER BY id;",
+            &movie_ids
+        )
+        .fetch_all(pool)
+        .await?;
+
+        // ids from the corpus are collected first, so numbered ids can't take them
+        let used_character_ids = sqlx::query_scalar!(
+            "SELECT source_id AS \"source_id!\" FROM character \
+            WHERE movie_id = ANY($1) AND source_id IS NOT NULL;",
+            &movie_ids
+        )
+        .fetch_all(pool)
+        .await?;
+        let used_line_ids = sqlx::query_scalar!(
+            "SELECT source_id AS \"source_id!\" FROM sentence \
+            WHERE movie_id = ANY($1) AND source_id IS NOT NULL;",
+            &movie_ids
+        )
+        .fetch_all(pool)
+        .await?;
+        let mut movie_corpus_ids = CorpusIds::new(
+            "m",
+            movies.iter().filter_map(|movie| movie.source_id.clone()),
+        );
+        let mut character_corpus_ids = CorpusIds::new("u", used_character_ids);
+        let mut line_corpus_ids = CorpusIds::new("L", used_line_ids);
+
+        let mut corpus = Vec::new();
+        for movie in movies {
+            let characters = sqlx::query!(
+                "SELECT id, name, source_id FROM character WHERE movie_id = $1 ORDER BY id;",
+                movie.id
+            )
+            .fetch_all(pool)
+            .await?;
+            let sentences = sqlx::query!(
+                "SELECT s.text, s.speaker_id AS \"speaker_id!\", s.conversation_id, s.source_id FROM sentence s \
+                LEFT JOIN track t ON t.id = s.track_id \
+                WHERE s.movie_id = $1 AND s.speaker_id IS NOT NULL \
+                AND t.kind IS DISTINCT FROM 'translation' \
+                ORDER BY s.position, s.id;",
+                movie.id
+            )
+            .fetch_all(pool)
+            .await?;
+            let conversations = sqlx::query!(
+                "SELECT id FROM conversation WHERE movie_id = $1 ORDER BY id;",
+                movie.id
+            )
+            .fetch_all(pool)
+            .await?;
+            let participants = sqlx::query!(
+                "SELECT cp.conversation_id AS \"conversation_id!\", cp.participant_id AS \"participant_id!\" \
+                FROM conversation_participants cp \
+                JOIN conversation c ON c.id = cp.conversation_id \
+                WHERE c.movie_id = $1 ORDER BY cp.participant_id;",
+                movie.id
+            )
+            .fetch_all(pool)
+            .await?;
+
+            // corpus ids of the characters by their id in the database
+            let mut corpus_character_ids: HashMap<i64, String> = HashMap::new();
+            let characters: Vec<CornellCharacter> = characters
+                .into_iter()
+                .map(|character| {
+                    let id = character_corpus_ids.id(character.source_id);
+                    corpus_character_ids.insert(character.id, id.clone());
+                    CornellCharacter {
+                        id,
+                        name: character.name,
+                    }
+                })
+                .collect();
+            let character_id = |id: i64| corpus_character_ids.get(&id).cloned();
+
+            let mut lines = Vec::new();
+            // line ids and speakers of every conversation, in the order of the sentences
+            let mut conversation_lines: HashMap<i64, Vec<(String, i64)>> = HashMap::new();
+            for sentence in sentences {
+                let id = line_corpus_ids.id(sentence.source_id);
+                if let Some(conversation_id) = sentence.conversation_id {
+                    conversation_lines
+                        .entry(conversation_id)
+                        .or_default()
+                        .push((id.clone(), sentence.speaker_id));
+                }
+                lines.push(CornellLine {
+                    id,
+                    character_id: character_id(sentence.speaker_id),
+                    text: sentence.text,
+                });
+            }
+
+            let conversations = conversations
+                .iter()
+                .filter_map(|conversation| {
+                    let lines = conversation_lines.remove(&conversation.id)?;
+                    let mut pair: Vec<String> = Vec::new();
+                    let speakers = lines.iter().map(|(_, speaker_id)| *speaker_id);
+                    let listed = participants
+                        .iter()
+                        .filter(|participant| participant.conversation_id == conversation.id)
+                        .map(|participant| participant.participant_id);
+                    for character_id in speakers.chain(listed).filter_map(character_id) {
+                        if !pair.contains(&character_id) {
+                            pair.push(character_id);
+                        }
+                    }
+                    let mut pair = pair.into_iter();
+                    match (pair.next(), pair.next()) {
+                        (Some(first), Some(second)) => Some(CornellConversation {
+                            first_character_id: first,
+                            second_character_id: second,
+                            line_ids: lines.into_iter().map(|(id, _)| id).collect(),
+                        }),
+                        _ => None,
+                    }
+                })
+                .collect();
+
+            corpus.push(CornellMovie {
+                id: movie_corpus_ids.id(movie.source_id),
+                title: movie.name,
+                characters,
+                lines,
+                conversations,
+            });
+        }
+
+        let output = cornell::write(&corpus);
+        Ok(CornellExport {
+            movie_titles_metadata: output.titles,
+            movie_characters_metadata: output.characters,
+            movie_lines: output.lines,
+            movie_conversations: output.conversations,
+        })
+    }
+}
+
+// hands out the ids of one kind of record, like "u0" for characters. Ids a row was
+// imported with are kept unless another row already took them, the others are numbered
+// after the highest number in use.
+struct CorpusIds {
+    prefix: &'static str,
+    taken: HashSet<String>,
+    next: u64,
+}
+
+impl CorpusIds {
+    fn new(prefix: &'static str, used: impl IntoIterator<Item = String>) -> Self {
+        let next = used
+            .into_iter()
+            .filter_map(|id| id.strip_prefix(prefix)?.parse::<u64>().ok())
+            .map(|number| number + 1)
+            .max()
+            .unwrap_or_default();
+        CorpusIds {
+            prefix,
+            taken: HashSet::new(),
+            next,
+        }
+    }
+
+    fn id(&mut self, source_id: Option<String>) -> String {
+        if let Some(id) = source_id {
+            if self.taken.insert(id.clone()) {
+                return id;
+            }
+        }
+        loop {
+            let id = format!("{}{}", self.prefix, self.next);
+            self.next += 1;
+            if self.taken.insert(id.clone()) {
+                return id;
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct CornellMutation;
+
+#[Object]
+impl CornellMutation {
+    /// Imports the Cornell Movie-Dialogs corpus, every movie as a new movie.
+    ///
+    /// Lines become untimed sentences ordered by their line id and every movie, character and
+    /// line keeps its id in the corpus for the export. Each conversation is named
+    /// after its first and last line id and its lines are directed to the other character
+    /// of the conversation. Every movie is imported in a transaction of its own.
+    // every argument is part of the GraphQL schema
+    #[allow(clippy::too_many_arguments)]
+    async fn import_cornell_corpus(
+        &self,
+        ctx: &Context<'_>,
+        movie_titles: Upload,
+        movie_characters: Upload,
+        movie_lines: Upload,
+        movie_conversations: Upload,
+        #[graphql(desc = "Only import these movies of the corpus, like \"m0\"")] movies: Option<
+            Vec<String>,
+        >,
+        #[graphql(desc = "Encoding of the files, detected if not given")] encoding: Option<String>,
+    ) -> Result<CornellImportResult, Error> {
+        let pool = ctx.data::<Pool<Postgres>>()?;
+        let encoding = encoding_for_label(encoding)?;
+        let mut texts = Vec::new();
+        for file in [
+            &movie_titles,
+            &movie_characters,
+            &movie_lines,
+            &movie_conversations,
+        ] {
+            let (_, bytes) = read_upload(ctx, file)?;
+            texts.push(encoding::decode(&bytes, encoding).0);
+        }
+        let (corpus, report) = cornell::parse(&CornellFiles {
+            titles: &texts[0],
+            characters: &texts[1],
+            lines: &texts[2],
+            conversations: &texts[3],
+        });
+
+        let mut imported = Vec::new();
+        for corpus_movie in corpus {
+            if let Some(movies) = &movies {
+                if !movies.contains(&corpus_movie.id) {
+                    continue;
+                }
+            }
+            imported.push(insert_movie(pool, corpus_movie).await?);
+        }
+
+        Ok(CornellImportResult {
+            movies: imported,
+            report,
+        })
+    }
+}
+
+// inserts a movie of the corpus with its characters, conversations and lines
+async fn insert_movie(
+    pool: &Pool<Postgres>,
+    corpus_movie: CornellMovie,
+) -> Result<CornellMovieImport, sqlx::Error> {
+    let mut transaction = pool.begin().await?;
+    let movie: Movie = sqlx::query_as!(
+        Movie,
+        "INSERT INTO movie (name, source_id) VALUES ($1, $2) RETURNING *;",
+        corpus_movie.title,
+        corpus_movie.id
+    )
+    .fetch_one(&mut transaction)
+    .await?;
+
+    let mut character_ids: HashMap<&str, i64> = HashMap::new();
+    for character in &corpus_movie.characters {
+        let id = sqlx::query_scalar!(
+            "INSERT INTO character (movie_id, name, source_id) VALUES ($1, $2, $3) RETURNING id;",
+            movie.id,
+            title_case(&character.name),
+            character.id
+        )
+        .fetch_one(&mut transaction)
+        .await?;
+        character_ids.insert(&character.id, id);
+    }
+
+    // the conversation and the characters of its pair for every line in a conversation
+    let mut line_conversations: HashMap<&str, (i64, [Option<i64>; 2])> = HashMap::new();
+    for conversation in &corpus_movie.conversations {
+        let name = match (conversation.line_ids.first(), conversation.line_ids.last()) {
+            (Some(first), Some(last)) if first != last => format!("{}-{}", first, last),
+            (Some(first), _) => first.clone(),
+            _ => continue,
+        };
+        let conversation_id = sqlx::query_scalar!(
+            "INSERT INTO conversation (name, movie_id) VALUES ($1, $2) RETURNING id;",
+            name,
+            movie.id
+        )
+        .fetch_one(&mut transaction)
+        .await?;
+
+        let pair = [
+            character_ids
+                .get(conversation.first_character_id.as_str())
+                .copied(),
+            character_ids
+                .get(conversation.second_character_id.as_str())
+                .copied(),
+        ];
+        for participant_id in pair.iter().flatten() {
+            sqlx::query!(
+                "INSERT INTO conversation_participants (conversation_id, participant_id) VALUES ($1, $2) \
+                ON CONFLICT DO NOTHING;",
+                conversation_id,
+                participant_id
+            )
+            .execute(&mut transaction)
+            .await?;
+        }
+        for line_id in &conversation.line_ids {
+            line_conversations.insert(line_id, (conversation_id, pair));
+        }
+    }
+
+    let new_sentences: Vec<NewSentence> = corpus_movie
+        .lines
+        .iter()
+        .enumerate()
+        .map(|(position, line)| NewSentence {
+            movie_id: movie.id,
+            track_id: None,
+            scene_id: None,
+            start_ms: None,
+            end_ms: None,
+            text: &line.text,
+            position: position as i64,
+            speaker_id: line
+                .character_id
+                .as_deref()
+                .and_then(|id| character_ids.get(id).copied()),
+            conversation_id: line_conversations
+                .get(line.id.as_str())
+                .map(|(conversation_id, _)| *conversation_id),
+            style: None,
+            parenthetical: None,
+            source_id: Some(&line.id),
+            markup: &[],
+        })
+        .collect();
+    let mut sentences = Vec::new();
+    for batch in new_sentences.chunks(BATCH_SIZE) {
+        sentences.extend(bulk::insert_sentences(&mut transaction, batch).await?);
+    }
+
+    // a line of a conversation is directed to the other character of the pair
+    let mut sentence_ids = Vec::new();
+    let mut directed_to_ids = Vec::new();
+    for (line, sentence) in corpus_movie.lines.iter().zip(&sentences) {
+        let (speaker_id, pair) = match (
+            sentence.speaker_id,
+            line_conversations.get(line.id.as_str()),
+        ) {
+            (Some(speaker_id), Some((_, pair))) => (speaker_id, pair),
+            _ => continue,
+        };
+        let listener_id = match pair {
+            [Some(first), Some(second)] if *first == speaker_id => Some(*second),
+            [Some(first), Some(second)] if *second == speaker_id => Some(*first),
+            _ => None,
+        };
+        if let Some(listener_id) = listener_id.filter(|id| *id != speaker_id) {
+            sentence_ids.push(sentence.id);
+            directed_to_ids.push(listener_id);
+        }
+    }
+    sqlx::query!(
+        "INSERT INTO sentence_directed_to (sentence_id, directed_to_id) \
+        SELECT * FROM UNNEST($1::BIGINT[], $2::BIGINT[]);",
+        &sentence_ids,
+        &directed_to_ids
+    )
+    .execute(&mut transaction)
+    .await?;
+
+    transaction.commit().await?;
+    Ok(CornellMovieImport {
+        corpus_id: corpus_movie.id,
+        movie,
+        character_count: corpus_movie.characters.len(),
+        sentence_count: sentences.len(),
+        conversation_count: corpus_movie.conversations.len(),
+    })
+}
@@ -0,0 +1,1263 @@
+use std::{collections::HashMap, io::Read};
+
+use async_graphql::*;
+use encoding_rs::Encoding;
+use sqlx::{Pool, Postgres, Transaction};
+
+use crate::{
+    align::{reimport, Timed},
+    parse::{
+        self,
+        markup::Markup,
+        script::{self, Screenplay, ScreenplayFormat},
+        ParseOptions, ParseReport, Sub, SubKind, SubtitleFormat,
+    },
+};
+
+use super::{
+    bulk::{self, NewSentence, NewSoundEvent, BATCH_SIZE},
+    character::Character,
+    job::{self, ImportJob, JobProgress, JobUpdates},
+    location::Location,
+    scene::Scene,
+    sentence::Sentence,
+    sound_event::SoundEvent,
+    track::{Track, TrackKind},
+};
+
+#[derive(Debug, SimpleObject)]
+pub struct ImportSummary {
+    pub movie_id: i64,
+    pub file_name: String,
+    pub format: SubtitleFormat,
+    /// The encoding the file was decoded with, either detected or given by the caller.
+    pub encoding: String,
+    pub sentence_count: usize,
+    pub sound_event_count: usize,
+}
+
+#[derive(Debug, SimpleObject)]
+pub struct ImportResult {
+    /// Whether this only shows what the import would do, without writing anything.
+    pub dry_run: bool,
+    /// What was imported, or would be in a dry run.
+    pub summary: ImportSummary,
+    /// The track the subtitles were imported into, none in a dry run into a new track.
+    pub track: Option<Track>,
+    /// Problems found while parsing, with the line they were found on.
+    pub report: ParseReport,
+    /// The parsed cues, only filled in a dry run.
+    pub cues: Vec<Sub>,
+    pub sentences: Vec<Sentence>,
+    /// Bracketed annotations that were imported as sound events instead of dialogue.
+    pub sound_events: Vec<SoundEvent>,
+    /// Speakers in the file that have no character yet, one is created for each unless
+    /// this is a dry run.
+    pub new_speakers: Vec<String>,
+    /// Characters that didn't exist yet and were created for the speakers in the file.
+    pub created_characters: Vec<Character>,
+}
+
+/// A sentence whose text was changed by a re-import.
+#[derive(Debug, SimpleObject)]
+pub struct UpdatedSentence {
+    pub sentence: Sentence,
+    pub previous_text: String,
+}
+
+#[derive(Debug, SimpleObject)]
+pub struct ReimportResult {
+    pub summary: ImportSummary,
+    pub track: Track,
+    /// Problems found while parsing, with the line they were found on.
+    pub report: ParseReport,
+    /// How many sentences kept their text and timing.
+    pub unchanged_count: usize,
+    /// How many sentences kept their text but got a new timing.
+    pub retimed_count: usize,
+    /// Sentences that kept their annotations but got the text of the new file.
+    pub updated: Vec<UpdatedSentence>,
+    /// Cues without a matching sentence, inserted as new sentences.
+    pub added: Vec<Sentence>,
+    /// Sentences without a matching cue. They are kept with their annotations until they
+    /// are deleted after review.
+    pub removed: Vec<Sentence>,
+    /// The sound events of the new file, which replace the previous ones.
+    pub sound_events: Vec<SoundEvent>,
+    /// Characters that didn't exist yet and were created for the speakers in the file.
+    pub created_characters: Vec<Character>,
+}
+
+#[derive(Debug, SimpleObject)]
+pub struct ScreenplaySummary {
+    pub movie_id: i64,
+    pub file_name: String,
+    pub format: ScreenplayFormat,
+    /// The encoding the file was decoded with, either detected or given by the caller.
+    pub encoding: String,
+    pub scene_count: usize,
+    /// How many locations were created, locations that already existed aren't counted.
+    pub location_count: usize,
+    /// How many characters were created, characters that already existed aren't counted.
+    pub character_count: usize,
+    pub sentence_count: usize,
+}
+
+#[derive(Debug, SimpleObject)]
+pub struct ScreenplayImportResult {
+    pub summary: ScreenplaySummary,
+    /// Problems found while parsing, with the line they were found on.
+    pub report: ParseReport,
+    pub scenes: Vec<Scene>,
+    /// Locations that didn't exist yet and were created for the scene headings.
+    pub created_locations: Vec<Location>,
+    /// Characters that didn't exist yet and were created for the character cues.
+    pub created_characters: Vec<Character>,
+    /// The dialogue, without timing until it is aligned with subtitles.
+    pub sentences: Vec<Sentence>,
+}
+
+// async-graphql implementations for ImportMutation
+
+#[derive(Default)]
+pub struct ImportMutation;
+
+#[Object]
+impl ImportMutation {
+    // every argument is part of the GraphQL schema
+    #[allow(clippy::too_many_arguments)]
+    async fn import_subtitles(
+        &self,
+        ctx: &Context<'_>,
+        movie_id: i64,
+        file: Upload,
+        #[graphql(desc = "Track to add the subtitles to, a new track is created when omitted")]
+        track_id: Option<i64>,
+        #[graphql(
+            desc = "Language tag of the new track like \"de\" or \"en\"",
+            default_with = "String::from(\"und\")"
+        )]
+        language: String,
+        #[graphql(desc = "Kind of the new track", default_with = "TrackKind::Original")]
+        kind: TrackKind,
+        #[graphql(desc = "Format of the file, detected from its name and content when omitted")]
+        format: Option<SubtitleFormat>,
+        #[graphql(
+            desc = "Encoding label such as \"utf-8\" or \"iso-8859-15\", detected when omitted"
+        )]
+        encoding: Option<String>,
+        options: Option<ParseOptions>,
+        #[graphql(
+            desc = "Only parse the file and show what would be imported, without writing anything",
+            default = false
+        )]
+        dry_run: bool,
+    ) -> Result<ImportResult, Error> {
+        let pool = ctx.data::<Pool<Postgres>>()?;
+        let encoding = encoding_for_label(encoding)?;
+        let (file_name, bytes) = read_upload(ctx, &file)?;
+
+        let options = options.unwrap_or_default();
+        let parsed = parse::parse(&bytes, &file_name, format, encoding, &options);
+        let diarized = parsed.format == SubtitleFormat::Transcript;
+        if dry_run {
+            let preview = preview_subs(pool, movie_id, track_id, diarized, &parsed.subs).await?;
+            let count = |kind| parsed.subs.iter().filter(|sub| sub.kind == kind).count();
+            return Ok(ImportResult {
+                dry_run,
+                summary: ImportSummary {
+                    movie_id,
+                    file_name,
+                    format: parsed.format,
+                    encoding: parsed.encoding.name().to_string(),
+                    sentence_count: count(SubKind::Dialogue),
+                    sound_event_count: count(SubKind::SoundEvent),
+                },
+                track: preview.track,
+                report: parsed.report,
+                cues: parsed.subs,
+                sentences: Vec::new(),
+                sound_events: Vec::new(),
+                new_speakers: preview.new_speakers,
+                created_characters: Vec::new(),
+            });
+        }
+
+        let new_track = NewTrack {
+            language,
+            kind,
+            source_file: file_name.clone(),
+        };
+        let inserted = insert_subs(
+            pool,
+            movie_id,
+            track_id,
+            new_track,
+            diarized,
+            &parsed.subs,
+            None,
+        )
+        .await?;
+        Ok(ImportResult {
+            dry_run,
+            summary: ImportSummary {
+                movie_id,
+                file_name,
+                format: parsed.format,
+                encoding: parsed.encoding.name().to_string(),
+                sentence_count: inserted.sentences.len(),
+                sound_event_count: inserted.sound_events.len(),
+            },
+            track: Some(inserted.track),
+            report: parsed.report,
+            cues: Vec::new(),
+            sentences: inserted.sentences,
+            sound_events: inserted.sound_events,
+            new_speakers: inserted
+                .created_characters
+                .iter()
+                .map(|character| character.name.clone())
+                .collect(),
+            created_characters: inserted.created_characters,
+        })
+    }
+
+    /// Starts importing subtitles in the background and returns the job right away. Its
+    /// progress can be followed with the `importJob` query and subscription.
+    #[allow(clippy::too_many_arguments)]
+    async fn start_subtitle_import(
+        &self,
+        ctx: &Context<'_>,
+        movie_id: i64,
+        file: Upload,
+        #[graphql(desc = "Track to add the subtitles to, a new track is created when omitted")]
+        track_id: Option<i64>,
+        #[graphql(
+            desc = "Language tag of the new track like \"de\" or \"en\"",
+            default_with = "String::from(\"und\")"
+        )]
+        language: String,
+        #[graphql(desc = "Kind of the new track", default_with = "TrackKind::Original")]
+        kind: TrackKind,
+        #[graphql(desc = "Format of the file, detected from its name and content when omitted")]
+        format: Option<SubtitleFormat>,
+        #[graphql(
+            desc = "Encoding label such as \"utf-8\" or \"iso-8859-15\", detected when omitted"
+        )]
+        encoding: Option<String>,
+        options: Option<ParseOptions>,
+    ) -> Result<ImportJob, Error> {
+        let pool = ctx.data::<Pool<Postgres>>()?;
+        let updates = ctx.data::<JobUpdates>()?;
+        let encoding = encoding_for_label(encoding)?;
+        let (file_name, bytes) = read_upload(ctx, &file)?;
+
+        let job = job::create(pool, movie_id, &file_name).await?;
+        let input = JobInput {
+            movie_id,
+            track_id,
+            new_track: NewTrack {
+                language,
+                kind,
+                source_file: file_name.clone(),
+            },
+            file_name,
+            bytes,
+            format,
+            encoding,
+            options: options.unwrap_or_default(),
+        };
+        tokio::spawn(run_job(pool.clone(), updates.clone(), job.id, input));
+        Ok(job)
+    }
+
+    /// Imports a new version of a subtitle track, keeping the annotations of the sentences
+    /// that match a cue of the new file by time and text.
+    async fn reimport_subtitles(
+        &self,
+        ctx: &Context<'_>,
+        track_id: i64,
+        file: Upload,
+        #[graphql(desc = "Format of the file, detected from its name and content when omitted")]
+        format: Option<SubtitleFormat>,
+        #[graphql(
+            desc = "Encoding label such as \"utf-8\" or \"iso-8859-15\", detected when omitted"
+        )]
+        encoding: Option<String>,
+        options: Option<ParseOptions>,
+    ) -> Result<ReimportResult, Error> {
+        let pool = ctx.data::<Pool<Postgres>>()?;
+        let encoding = encoding_for_label(encoding)?;
+        let (file_name, bytes) = read_upload(ctx, &file)?;
+
+        let options = options.unwrap_or_default();
+        let parsed = parse::parse(&bytes, &file_name, format, encoding, &options);
+        let diarized = parsed.format == SubtitleFormat::Transcript;
+        let reimported = reimport_subs(pool, track_id, &file_name, diarized, &parsed.subs).await?;
+        Ok(ReimportResult {
+            summary: ImportSummary {
+                movie_id: reimported.track.movie_id,
+                file_name,
+                format: parsed.format,
+                encoding: parsed.encoding.name().to_string(),
+                sentence_count: parsed
+                    .subs
+                    .iter()
+                    .filter(|sub| sub.kind == SubKind::Dialogue)
+                    .count(),
+                sound_event_count: reimported.sound_events.len(),
+            },
+            track: reimported.track,
+            report: parsed.report,
+            unchanged_count: reimported.unchanged_count,
+            retimed_count: reimported.retimed_count,
+            updated: reimported.updated,
+            added: reimported.added,
+            removed: reimported.removed,
+            sound_events: reimported.sound_events,
+            created_characters: reimported.created_characters,
+        })
+    }
+
+    async fn import_screenplay(
+        &self,
+        ctx: &Context<'_>,
+        movie_id: i64,
+        file: Upload,
+        #[graphql(desc = "Format of the file, detected from its name and content when omitted")]
+        format: Option<ScreenplayFormat>,
+        #[graphql(
+            desc = "Encoding label such as \"utf-8\" or \"iso-8859-15\", detected when omitted"
+        )]
+        encoding: Option<String>,
+    ) -> Result<ScreenplayImportResult, Error> {
+        let pool = ctx.data::<Pool<Postgres>>()?;
+        let encoding = encoding_for_label(encoding)?;
+        let (file_name, bytes) = read_upload(ctx, &file)?;
+
+        let parsed = script::parse(&bytes, &file_name, format, encoding);
+        let inserted = insert_screenplay(pool, movie_id, &parsed.screenplay).await?;
+        Ok(ScreenplayImportResult {
+            summary: ScreenplaySummary {
+                movie_id,
+                file_name,
+                format: parsed.format,
+                encoding: parsed.encoding.name().to_string(),
+                scene_count: inserted.scenes.len(),
+                location_count: inserted.created_locations.len(),
+                character_count: inserted.created_characters.len(),
+                sentence_count: inserted.sentences.len(),
+            },
+            report: parsed.report,
+            scenes: inserted.scenes,
+            created_locations: inserted.created_locations,
+            created_characters: inserted.created_characters,
+            sentences: inserted.sentences,
+        })
+    }
+}
+
+pub(super) fn encoding_for_label(
+    label: Option<String>,
+) -> Result<Option<&'static Encoding>, Error> {
+    label
+        .map(|label| {
+            Encoding::for_label(label.trim().as_bytes())
+                .ok_or_else(|| Error::new(format!("Unknown encoding: {}", label)))
+        })
+        .transpose()
+}
+
+// returns the file name and content of an uploaded file
+pub(super) fn read_upload(ctx: &Context<'_>, file: &Upload) -> Result<(String, Vec<u8>), Error> {
+    let upload = file.value(ctx)?;
+    let file_name = upload.filename.clone();
+
+    let mut bytes = Vec::new();
+    upload.into_read().read_to_end(&mut bytes)?;
+    Ok((file_name, bytes))
+}
+
+// a track to create if subtitles aren't imported into an existing one
+struct NewTrack {
+    language: String,
+    kind: TrackKind,
+    source_file: String,
+}
+
+// everything a background import needs from the request
+struct JobInput {
+    movie_id: i64,
+    track_id: Option<i64>,
+    new_track: NewTrack,
+    file_name: String,
+    bytes: Vec<u8>,
+    format: Option<SubtitleFormat>,
+    encoding: Option<&'static Encoding>,
+    options: ParseOptions,
+}
+
+// runs an import job and marks it as failed if the import panics
+async fn run_job(pool: Pool<Postgres>, updates: JobUpdates, job_id: i64, input: JobInput) {
+    let import = tokio::spawn(import_job(pool.clone(), updates.clone(), job_id, input));
+    if let Err(error) = import.await {
+        log::error!("Import job {} stopped: {}", job_id, error);
+        let progress = JobProgress {
+            pool: &pool,
+            updates: &updates,
+            job_id,
+        };
+        if let Err(error) = progress.fail("The import stopped unexpectedly").await {
+            log::error!("Failed to finish import job {}: {}", job_id, error);
+        }
+    }
+}
+
+// parses and inserts the file of an import job, recording progress and outcome on the job
+async fn import_job(pool: Pool<Postgres>, updates: JobUpdates, job_id: i64, input: JobInput) {
+    let progress = JobProgress {
+        pool: &pool,
+        updates: &updates,
+        job_id,
+    };
+
+    let parsed = parse::parse(
+        &input.bytes,
+        &input.file_name,
+        input.format,
+        input.encoding,
+        &input.options,
+    );
+    let diarized = parsed.format == SubtitleFormat::Transcript;
+    let result = match progress.start(parsed.subs.len(), &parsed.report).await {
+        Ok(()) => insert_subs(
+            &pool,
+            input.movie_id,
+            input.track_id,
+            input.new_track,
+            diarized,
+            &parsed.subs,
+            Some(&progress),
+        )
+        .await
+        .map(|inserted| inserted.track.id),
+        Err(error) => Err(error),
+    };
+
+    if let Err(error) = progress.finish(result).await {
+        log::error!("Failed to finish import job {}: {}", job_id, error);
+    }
+}
+
+// what insert_subs would do, found without writing anything
+struct Preview {
+    track: Option<Track>,
+    new_speakers: Vec<String>,
+}
+
+async fn preview_subs(
+    pool: &Pool<Postgres>,
+    movie_id: i64,
+    track_id: Option<i64>,
+    diarized: bool,
+    subs: &[Sub],
+) -> Result<Preview, sqlx::Error> {
+    // only reads, it's a transaction for Speakers and is rolled back when dropped
+    let mut transaction = pool.begin().await?;
+    let track = match track_id {
+        Some(track_id) => Some(
+            sqlx::query_as!(
+                Track,
+                "SELECT id, language, kind as \"kind: TrackKind\", source_file, movie_id FROM track \
+                WHERE id = $1 AND movie_id = $2;",
+                track_id,
+                movie_id
+            )
+            .fetch_one(&mut transaction)
+            .await?,
+        ),
+        None => None,
+    };
+    let speakers = if diarized {
+        Speakers::placeholders(
+            &mut transaction,
+            movie_id,
+            track.as_ref().map(|track| track.id),
+            "",
+        )
+        .await?
+    } else {
+        Speakers::load(&mut transaction, movie_id).await?
+    };
+
+    let mut new_speakers: Vec<String> = Vec::new();
+    for name in subs
+        .iter()
+        .filter(|sub| sub.kind == SubKind::Dialogue)
+        .filter_map(|sub| sub.speaker.as_ref())
+    {
+        let known = speakers.knows(name)
+            || new_speakers
+                .iter()
+                .any(|new| new.to_lowercase() == name.to_lowercase());
+        if !known {
+            new_speakers.push(name.clone());
+        }
+    }
+
+    Ok(Preview {
+        track,
+        new_speakers,
+    })
+}
+
+// what insert_subs wrote to the database
+struct Inserted {
+    track: Track,
+    sentences: Vec<Sentence>,
+    sound_events: Vec<SoundEvent>,
+    created_characters: Vec<Character>,
+}
+
+// inserts the parsed cues as sentences and sound events of the movie in batches, either
+// all of them or none
+async fn insert_subs(
+    pool: &Pool<Postgres>,
+    movie_id: i64,
+    track_id: Option<i64>,
+    new_track: NewTrack,
+    diarized: bool,
+    subs: &[Sub],
+    progress: Option<&JobProgress<'_>>,
+) -> Result<Inserted, sqlx::Error> {
+    let mut transaction = pool.begin().await?;
+    let track: Track = match track_id {
+        Some(track_id) => {
+            sqlx::query_as!(
+            Track,
+            "SELECT id, language, kind as \"kind: TrackKind\", source_file, movie_id FROM track \
+                WHERE id = $1 AND movie_id = $2;",
+            track_id,
+            movie_id
+        )
+            .fetch_one(&mut transaction)
+            .await?
+        }
+        None => {
+            sqlx::query_as!(
+                Track,
+                "INSERT INTO track (movie_id, language, kind, source_file) VALUES ($1, $2, $3, $4) \
+                RETURNING id, language, kind as \"kind: TrackKind\", source_file, movie_id;",
+                movie_id,
+                new_track.language,
+                new_track.kind as TrackKind,
+                new_track.source_file
+            )
+            .fetch_one(&mut transaction)
+            .await?
+        }
+    };
+    // an existing track keeps its lines, new ones go after them
+    let first_position = sqlx::query_scalar!(
+        "SELECT COALESCE(MAX(position) + 1, 0) AS \"position!\" FROM sentence WHERE track_id = $1;",
+        track.id
+    )
+    .fetch_one(&mut transaction)
+    .await?;
+    let first_sound_event_position = sqlx::query_scalar!(
+        "SELECT COALESCE(MAX(position) + 1, 0) AS \"position!\" FROM sound_event WHERE track_id = $1;",
+        track.id
+    )
+    .fetch_one(&mut transaction)
+    .await?;
+    let mut speakers = if diarized {
+        Speakers::placeholders(
+            &mut transaction,
+            movie_id,
+            Some(track.id),
+            &new_track.source_file,
+        )
+        .await?
+    } else {
+        Speakers::load(&mut transaction, movie_id).await?
+    };
+    // speakers first, they may need new characters
+    let dialogue: Vec<&Sub> = subs
+        .iter()
+        .filter(|sub| sub.kind == SubKind::Dialogue)
+        .collect();
+    let mut speaker_ids = Vec::with_capacity(dialogue.len());
+    for sub in &dialogue {
+        speaker_ids.push(match &sub.speaker {
+            Some(name) => Some(speakers.resolve(&mut transaction, name).await?),
+            None => None,
+        });
+    }
+
+    let new_sentences: Vec<NewSentence> = dialogue
+        .iter()
+        .zip(speaker_ids)
+        .enumerate()
+        .map(|(position, (sub, speaker_id))| NewSentence {
+            movie_id,
+            track_id: Some(track.id),
+            scene_id: None,
+            start_ms: Some(sub.start),
+            end_ms: Some(sub.end),
+            text: &sub.text,
+            position: first_position + position as i64,
+            speaker_id,
+            conversation_id: None,
+            style: sub.style.as_deref(),
+            parenthetical: None,
+            source_id: None,
+            markup: &sub.markup,
+        })
+        .collect();
+    let new_sound_events: Vec<NewSoundEvent> = subs
+        .iter()
+        .filter(|sub| sub.kind == SubKind::SoundEvent)
+        .enumerate()
+        .map(|(position, sub)| NewSoundEvent {
+            movie_id,
+            track_id: track.id,
+            start_ms: sub.start,
+            end_ms: sub.end,
+            text: &sub.text,
+            position: first_sound_event_position + position as i64,
+        })
+        .collect();
+
+    let mut sentences = Vec::with_capacity(new_sentences.len());
+    for batch in new_sentences.chunks(BATCH_SIZE) {
+        sentences.extend(bulk::insert_sentences(&mut transaction, batch).await?);
+        if let Some(progress) = progress {
+            progress.update(sentences.len()).await?;
+        }
+    }
+    let mut sound_events = Vec::with_capacity(new_sound_events.len());
+    for batch in new_sound_events.chunks(BATCH_SIZE) {
+        sound_events.extend(bulk::insert_sound_events(&mut transaction, batch).await?);
+        if let Some(progress) = progress {
+            progress
+                .update(sentences.len() + sound_events.len())
+                .await?;
+        }
+    }
+    transaction.commit().await?;
+
+    Ok(Inserted {
+        track,
+        sentences,
+        sound_events,
+        created_characters: speakers.created,
+    })
+}
+
+// what reimport_subs changed in the database
+struct Reimported {
+    track: Track,
+    unchanged_count: usize,
+    retimed_count: usize,
+    updated: Vec<UpdatedSentence>,
+    added: Vec<Sentence>,
+    removed: Vec<Sentence>,
+    sound_events: Vec<SoundEvent>,
+    created_characters: Vec<Character>,
+}
+
+// replaces the sentences of the track with the parsed cues, either all of them or
+// none. Sentences matching a cue are updated in place so their speaker, conversation and
+// addressees survive, cues without a match are inserted and sentences without a match are
+// left alone. Positions follow the new file, with removed sentences kept where they were.
+async fn reimport_subs(
+    pool: &Pool<Postgres>,
+    track_id: i64,
+    file_name: &str,
+    diarized: bool,
+    subs: &[Sub],
+) -> Result<Reimported, sqlx::Error> {
+    let (dialogue, events): (Vec<&Sub>, Vec<&Sub>) =
+        subs.iter().partition(|sub| sub.kind == SubKind::Dialogue);
+
+    let mut transaction = pool.begin().await?;
+    let track: Track = sqlx::query_as!(
+        Track,
+        "UPDATE track SET source_file = $1 WHERE id = $2 \
+        RETURNING id, language, kind as \"kind: TrackKind\", source_file, movie_id;",
+        file_name,
+        track_id
+    )
+    .fetch_one(&mut transaction)
+    .await?;
+    let movie_id = track.movie_id;
+    let existing = sqlx::query_as!(
+        Sentence,
+        "SELECT * FROM sentence WHERE track_id = $1 ORDER BY start_ms, position;",
+        track_id
+    )
+    .fetch_all(&mut transaction)
+    .await?;
+
+    let pairs = {
+        let old: Vec<Timed> = existing
+            .iter()
+            .map(|sentence| Timed {
+                start: sentence.start_ms.unwrap_or_default(),
+                end: sentence.end_ms.unwrap_or_default(),
+                text: &sentence.text,
+            })
+            .collect();
+        let new: Vec<Timed> = dialogue
+            .iter()
+            .map(|sub| Timed {
+                start: sub.start,
+                end: sub.end,
+                text: &sub.text,
+            })
+            .collect();
+        reimport::diff(&old, &new)
+    };
+    let (old_len, new_len) = (existing.len(), dialogue.len());
+
+    let mut speakers = if diarized {
+        let mut speakers =
+            Speakers::placeholders(&mut transaction, movie_id, Some(track_id), file_name).await?;
+        // the placeholder of a label may have been merged into a real character since, who
+        // then speaks its matched lines
+        for (old_index, new_index) in &pairs {
+            if let (Some(name), Some(speaker_id)) = (
+                &dialogue[*new_index].speaker,
+                existing[*old_index].speaker_id,
+            ) {
+                speakers.seed(name, speaker_id);
+            }
+        }
+        speakers
+    } else {
+        Speakers::load(&mut transaction, movie_id).await?
+    };
+
+    let mut reimported = Reimported {
+        track,
+        unchanged_count: 0,
+        retimed_count: 0,
+        updated: Vec::new(),
+        added: Vec::new(),
+        removed: Vec::new(),
+        sound_events: Vec::new(),
+        created_characters: Vec::new(),
+    };
+    let mut existing = existing.into_iter().map(Some).collect::<Vec<_>>();
+    // the changes are collected and written with one statement per kind at the end
+    let mut added = Vec::new();
+    let mut matched: Vec<Sentence> = Vec::new();
+    let mut updated_markup: Vec<(i64, &Markup)> = Vec::new();
+    let (mut next_old, mut next_new) = (0, 0);
+    let mut position = 0;
+    for (old_index, new_index) in pairs
+        .into_iter()
+        .map(|(old_index, new_index)| (Some(old_index), Some(new_index)))
+        .chain(std::iter::once((None, None)))
+    {
+        // sentences and cues between two matches were removed or added
+        while next_old < old_index.unwrap_or(old_len) {
+            let sentence = existing[next_old]
+                .take()
+                .expect("every sentence is visited once");
+            reimported.removed.push(Sentence {
+                position,
+                ..sentence
+            });
+            next_old += 1;
+            position += 1;
+        }
+        while next_new < new_index.unwrap_or(new_len) {
+            let sub = dialogue[next_new];
+            let speaker_id = match &sub.speaker {
+                Some(name) => Some(speakers.resolve(&mut transaction, name).await?),
+                None => None,
+            };
+            added.push(NewSentence {
+                movie_id,
+                track_id: Some(track_id),
+                scene_id: None,
+                start_ms: Some(sub.start),
+                end_ms: Some(sub.end),
+                text: &sub.text,
+                position,
+                speaker_id,
+                conversation_id: None,
+                style: sub.style.as_deref(),
+                parenthetical: None,
+                source_id: None,
+                markup: &sub.markup,
+            });
+            next_new += 1;
+            position += 1;
+        }
+
+        let (Some(old_index), Some(new_index)) = (old_index, new_index) else {
+            break;
+        };
+        let previous = existing[old_index]
+            .take()
+            .expect("every sentence is visited once");
+        let sub = dialogue[new_index];
+        // a speaker from the file only fills in a missing one
+        let speaker_id = match (previous.speaker_id, &sub.speaker) {
+            (None, Some(name)) => Some(speakers.resolve(&mut transaction, name).await?),
+            (speaker_id, _) => speaker_id,
+        };
+        let sentence = Sentence {
+            start_ms: Some(sub.start),
+            end_ms: Some(sub.end),
+            text: sub.text.clone(),
+            position,
+            speaker_id,
+            style: sub.style.clone(),
+            ..previous
+        };
+        updated_markup.extend(sub.markup.iter().map(|markup| (sentence.id, markup)));
+
+        if previous.text != sentence.text {
+            reimported.updated.push(UpdatedSentence {
+                sentence: sentence.clone(),
+                previous_text: previous.text,
+            });
+        } else if previous.start_ms != sentence.start_ms || previous.end_ms != sentence.end_ms {
+            reimported.retimed_count += 1;
+        } else {
+            reimported.unchanged_count += 1;
+        }
+        matched.push(sentence);
+        next_old = old_index + 1;
+        next_new = new_index + 1;
+        position += 1;
+    }
+
+    let removed_ids: Vec<i64> = reimported.removed.iter().map(|s| s.id).collect();
+    let removed_positions: Vec<i64> = reimported.removed.iter().map(|s| s.position).collect();
+    sqlx::query!(
+        "UPDATE sentence SET position = u.position \
+        FROM UNNEST($1::BIGINT[], $2::BIGINT[]) as u(id, position) \
+        WHERE sentence.id = u.id;",
+        &removed_ids,
+        &removed_positions
+    )
+    .execute(&mut transaction)
+    .await?;
+
+    let matched_ids: Vec<i64> = matched.iter().map(|s| s.id).collect();
+    let starts: Vec<Option<i64>> = matched.iter().map(|s| s.start_ms).collect();
+    let ends: Vec<Option<i64>> = matched.iter().map(|s| s.end_ms).collect();
+    let texts: Vec<&str> = matched.iter().map(|s| s.text.as_str()).collect();
+    let positions: Vec<i64> = matched.iter().map(|s| s.position).collect();
+    let speaker_ids: Vec<Option<i64>> = matched.iter().map(|s| s.speaker_id).collect();
+    let styles: Vec<Option<&str>> = matched.iter().map(|s| s.style.as_deref()).collect();
+    sqlx::query!(
+        "UPDATE sentence SET start_ms = u.start_ms, end_ms = u.end_ms, text = u.text, \
+        position = u.position, speaker_id = u.speaker_id, style = u.style \
+        FROM UNNEST($1::BIGINT[], $2::BIGINT[], $3::BIGINT[], $4::TEXT[], $5::BIGINT[], $6::BIGINT[], $7::VARCHAR[]) \
+        as u(id, start_ms, end_ms, text, position, speaker_id, style) \
+        WHERE sentence.id = u.id;",
+        &matched_ids,
+        &starts as _,
+        &ends as _,
+        &texts as _,
+        &positions,
+        &speaker_ids as _,
+        &styles as _
+    )
+    .execute(&mut transaction)
+    .await?;
+
+    for batch in added.chunks(BATCH_SIZE) {
+        reimported
+            .added
+            .extend(bulk::insert_sentences(&mut transaction, batch).await?);
+    }
+    sqlx::query!(
+        "DELETE FROM sentence_markup WHERE sentence_id = ANY($1);",
+        &matched_ids
+    )
+    .execute(&mut transaction)
+    .await?;
+    for batch in updated_markup.chunks(BATCH_SIZE) {
+        bulk::insert_markup(&mut transaction, batch).await?;
+    }
+
+    // sound events carry no annotations, so the new ones simply replace the old ones
+    sqlx::query!("DELETE FROM sound_event WHERE track_id = $1;", track_id)
+        .execute(&mut transaction)
+        .await?;
+    let new_sound_events: Vec<NewSoundEvent> = events
+        .iter()
+        .enumerate()
+        .map(|(position, sub)| NewSoundEvent {
+            movie_id,
+            track_id,
+            start_ms: sub.start,
+            end_ms: sub.end,
+            text: &sub.text,
+            position: position as i64,
+        })
+        .collect();
+    for batch in new_sound_events.chunks(BATCH_SIZE) {
+        reimported
+            .sound_events
+            .extend(bulk::insert_sound_events(&mut transaction, batch).await?);
+    }
+    transaction.commit().await?;
+
+    reimported.created_characters = speakers.created;
+    Ok(reimported)
+}
+
+// what insert_screenplay wrote to the database
+struct InsertedScreenplay {
+    scenes: Vec<Scene>,
+    created_locations: Vec<Location>,
+    created_characters: Vec<Character>,
+    sentences: Vec<Sentence>,
+}
+
+// inserts the scenes of the screenplay with their dialogue as untimed sentences, either
+// all of them or none. Locations are matched by name and only created if they are new.
+async fn insert_screenplay(
+    pool: &Pool<Postgres>,
+    movie_id: i64,
+    screenplay: &Screenplay,
+) -> Result<InsertedScreenplay, sqlx::Error> {
+    let mut scenes = Vec::with_capacity(screenplay.scenes.len());
+    let mut created_locations = Vec::new();
+    let mut new_sentences = Vec::new();
+
+    let mut transaction = pool.begin().await?;
+    let mut speakers = Speakers::load(&mut transaction, movie_id).await?;
+    let mut locations: HashMap<String, i64> = sqlx::query_as!(
+        Location,
+        "SELECT * FROM location WHERE movie_id = $1;",
+        movie_id
+    )
+    .fetch_all(&mut transaction)
+    .await?
+    .into_iter()
+    .map(|location| (location.name.to_lowercase(), location.id))
+    .collect();
+
+    for script_scene in &screenplay.scenes {
+        let location_id = match &script_scene.location {
+            Some(name) => match locations.get(&name.to_lowercase()) {
+                Some(id) => Some(*id),
+                None => {
+                    let location: Location = sqlx::query_as!(
+                        Location,
+                        "INSERT INTO location (movie_id, name) VALUES ($1, $2) RETURNING *;",
+                        movie_id,
+                        name
+                    )
+                    .fetch_one(&mut transaction)
+                    .await?;
+                    locations.insert(name.to_lowercase(), location.id);
+                    let id = location.id;
+                    created_locations.push(location);
+                    Some(id)
+                }
+            },
+            None => None,
+        };
+
+        let scene_id = match &script_scene.heading {
+            Some(heading) => {
+                let scene: Scene = sqlx::query_as!(
+                    Scene,
+                    "INSERT INTO scene (name, location_id, movie_id) VALUES ($1, $2, $3) RETURNING *;",
+                    heading,
+                    location_id,
+                    movie_id
+                )
+                .fetch_one(&mut transaction)
+                .await?;
+                let id = scene.id;
+                scenes.push(scene);
+                Some(id)
+            }
+            None => None,
+        };
+
+        for line in &script_scene.lines {
+            let speaker_id = speakers.resolve(&mut transaction, &line.speaker).await?;
+            new_sentences.push(NewSentence {
+                movie_id,
+                track_id: None,
+                scene_id,
+                start_ms: None,
+                end_ms: None,
+                text: &line.text,
+                position: new_sentences.len() as i64,
+                speaker_id: Some(speaker_id),
+                conversation_id: None,
+                style: None,
+                parenthetical: line.parenthetical.as_deref(),
+                source_id: None,
+                markup: &line.markup,
+            });
+        }
+    }
+
+    let mut sentences = Vec::with_capacity(new_sentences.len());
+    for batch in new_sentences.chunks(BATCH_SIZE) {
+        sentences.extend(bulk::insert_sentences(&mut transaction, batch).await?);
+    }
+    transaction.commit().await?;
+
+    Ok(InsertedScreenplay {
+        scenes,
+        created_locations,
+        created_characters: speakers.created,
+        sentences,
+    })
+}
+
+/// Matches speaker names from subtitle files and screenplays to the characters of a movie.
+///
+/// Names are compared case insensitively against the character names and their aliases,
+/// characters that don't exist yet are created.
+struct Speakers {
+    movie_id: i64,
+    ids: HashMap<String, i64>,
+    created: Vec<Character>,
+    // the description of placeholder characters, when the names are diarization labels
+    placeholders: Option<String>,
+}
+
+impl Speakers {
+    async fn load(
+        transaction: &mut Transaction<'_, Postgres>,
+        movie_id: i64,
+    ) -> Result<Speakers, sqlx::Error> {
+        let characters = sqlx::query_as!(
+            Character,
+            "SELECT * FROM character WHERE movie_id = $1;",
+            movie_id
+        )
+        .fetch_all(&mut *transaction)
+        .await?;
+
+        let aliases = sqlx::query!(
+            "SELECT a.character_id, a.alias FROM character_alias as a \
+            INNER JOIN character as c ON c.id = a.character_id \
+            WHERE c.movie_id = $1;",
+            movie_id
+        )
+        .fetch_all(&mut *transaction)
+        .await?;
+
+        // a character's own name wins over an alias someone else has
+        let ids = aliases
+            .into_iter()
+            .map(|alias| (alias.alias.to_lowercase(), alias.character_id))
+            .chain(
+                characters
+                    .into_iter()
+                    .map(|character| (character.name.to_lowercase(), character.id)),
+            )
+            .collect();
+
+        Ok(Speakers {
+            movie_id,
+            ids,
+            created: Vec::new(),
+            placeholders: None,
+        })
+    }
+
+    // labels like "SPEAKER_01" only mean something within one transcript, so they are only
+    // matched to the placeholders already speaking on its track and get new placeholder
+    // characters otherwise
+    async fn placeholders(
+        transaction: &mut Transaction<'_, Postgres>,
+        movie_id: i64,
+        track_id: Option<i64>,
+        file_name: &str,
+    ) -> Result<Speakers, sqlx::Error> {
+        let characters = sqlx::query!(
+            "SELECT DISTINCT c.id, c.name FROM character as c \
+            INNER JOIN sentence as s ON s.speaker_id = c.id \
+            WHERE s.track_id = $1 AND c.placeholder;",
+            track_id
+        )
+        .fetch_all(&mut *transaction)
+        .await?;
+
+        Ok(Speakers {
+            movie_id,
+            ids: characters
+                .into_iter()
+                .map(|character| (character.name.to_lowercase(), character.id))
+                .collect(),
+            created: Vec::new(),
+            placeholders: Some(format!("Diarized speaker in {}", file_name)),
+        })
+    }
+
+    // makes a name resolve to a character unless it already resolves to one
+    fn seed(&mut self, name: &str, id: i64) {
+        self.ids.entry(name.to_lowercase()).or_insert(id);
+    }
+
+    fn knows(&self, name: &str) -> bool {
+        self.ids.contains_key(&name.to_lowercase())
+    }
+
+    async fn resolve(
+        &mut self,
+        transaction: &mut Transaction<'_, Postgres>,
+        name: &str,
+    ) -> Result<i64, sqlx::Error> {
+        if let Some(id) = self.ids.get(&name.to_lowercase()) {
+            return Ok(*id);
+        }
+
+        let character: Character = sqlx::query_as!(
+            Character,
+            "INSERT INTO character (movie_id, name, description, placeholder) VALUES ($1, $2, $3, $4) RETURNING *;",
+            self.movie_id,
+            name,
+            self.placeholders,
+            self.placeholders.is_some()
+        )
+        .fetch_one(&mut *transaction)
+        .await?;
+
+        self.ids.insert(name.to_lowercase(), character.id);
+        let id = character.id;
+        self.created.push(character);
+        Ok(id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{MutationRoot, QueryRoot, SubscriptionRoot};
+
+    fn sub(start: i64, text: &str, speaker: &str) -> Sub {
+        Sub {
+            kind: SubKind::Dialogue,
+            index: None,
+            line: 0,
+            start,
+            end: start + 500,
+            text: text.to_string(),
+            speaker: Some(speaker.to_string()),
+            style: None,
+            markup: Vec::new(),
+        }
+    }
+
+    fn transcript() -> NewTrack {
+        NewTrack {
+            language: "en".to_string(),
+            kind: TrackKind::Original,
+            source_file: "a.json".to_string(),
+        }
+    }
+
+    async fn speakers(pool: &Pool<Postgres>, track_id: i64) -> Vec<(String, String)> {
+        sqlx::query!(
+            "SELECT s.text, c.name FROM sentence as s \
+            INNER JOIN character as c ON c.id = s.speaker_id \
+            WHERE s.track_id = $1 ORDER BY s.position;",
+            track_id
+        )
+        .fetch_all(pool)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|row| (row.text, row.name))
+        .collect()
+    }
+
+    #[sqlx::test]
+    async fn reimports_diarized_speakers_as_their_characters(pool: Pool<Postgres>) {
+        let movie_id =
+            sqlx::query_scalar!("INSERT INTO movie (name) VALUES ('Film') RETURNING id;")
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        let subs = vec![
+            sub(1000, "Hello.", "SPEAKER_00"),
+            sub(2000, "Hi.", "SPEAKER_01"),
+        ];
+        let inserted = insert_subs(&pool, movie_id, None, transcript(), true, &subs, None)
+            .await
+            .unwrap();
+        assert_eq!(inserted.created_characters.len(), 2);
+        let track_id = inserted.track.id;
+
+        // the first speaker turns out to be Anna
+        let anna_id = sqlx::query_scalar!(
+            "INSERT INTO character (movie_id, name) VALUES ($1, 'Anna') RETURNING id;",
+            movie_id
+        )
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        let schema = Schema::build(
+            QueryRoot::default(),
+            MutationRoot::default(),
+            SubscriptionRoot::default(),
+        )
+        .data(pool.clone())
+        .finish();
+        let response = schema
+            .execute(format!(
+                "mutation {{ mergeCharacters(id: {}, intoId: {}) {{ id }} }}",
+                inserted.created_characters[0].id, anna_id
+            ))
+            .await;
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+
+        let subs = vec![
+            sub(1000, "Hello.", "SPEAKER_00"),
+            sub(2000, "Hi.", "SPEAKER_01"),
+            sub(3000, "How are you?", "SPEAKER_00"),
+            sub(4000, "Fine.", "SPEAKER_01"),
+        ];
+        let reimported = reimport_subs(&pool, track_id, "b.json", true, &subs)
+            .await
+            .unwrap();
+        assert_eq!(reimported.added.len(), 2);
+        assert!(reimported.created_characters.is_empty());
+
+        let inserted = insert_subs(
+            &pool,
+            movie_id,
+            Some(track_id),
+            transcript(),
+            true,
+            &[sub(5000, "Bye.", "SPEAKER_01")],
+            None,
+        )
+        .await
+        .unwrap();
+        assert!(inserted.created_characters.is_empty());
+
+        let expected = [
+            ("Hello.", "Anna"),
+            ("Hi.", "SPEAKER_01"),
+            ("How are you?", "Anna"),
+            ("Fine.", "SPEAKER_01"),
+            ("Bye.", "SPEAKER_01"),
+        ];
+        assert_eq!(
+            speakers(&pool, track_id).await,
+            expected
+                .iter()
+                .map(|(text, name)| (text.to_string(), name.to_string()))
+                .collect::<Vec<_>>()
+        );
+    }
+}
@@ -0,0 +1,334 @@
+use async_graphql::{futures_util::stream, futures_util::Stream, *};
+use serde::{Deserialize, Serialize};
+use sqlx::{Pool, Postgres};
+use tokio::sync::broadcast::{self, error::RecvError};
+
+use crate::parse::{ParseIssue, ParseReport};
+
+use super::{movie::Movie, track::Track, track::TrackKind};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Enum, sqlx::Type)]
+#[sqlx(type_name = "job_status", rename_all = "lowercase")]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Succeeded,
+    Failed,
+}
+
+/// A subtitle import running in the background.
+#[derive(Debug, Clone, Serialize, Deserialize, SimpleObject)]
+#[graphql(complex)]
+pub struct ImportJob {
+    pub id: i64,
+    pub file_name: String,
+    pub status: JobStatus,
+    /// How many cues are imported so far.
+    pub processed: i64,
+    /// How many cues the file has, known once it is parsed.
+    pub total: i64,
+    /// Why the import failed.
+    pub error: Option<String>,
+    #[graphql(skip)]
+    pub movie_id: i64,
+    #[graphql(skip)]
+    pub track_id: Option<i64>,
+}
+
+impl ImportJob {
+    pub fn finished(&self) -> bool {
+        matches!(self.status, JobStatus::Succeeded | JobStatus::Failed)
+    }
+}
+
+/// Sends every change of an import job to the subscriptions watching it.
+#[derive(Clone)]
+pub struct JobUpdates(broadcast::Sender<ImportJob>);
+
+impl JobUpdates {
+    pub fn new() -> JobUpdates {
+        JobUpdates(broadcast::channel(256).0)
+    }
+
+    // nobody listening isn't an error
+    pub fn send(&self, job: &ImportJob) {
+        self.0.send(job.clone()).ok();
+    }
+}
+
+impl Default for JobUpdates {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// SQLx and async-graphql implementations for ImportJob
+
+#[ComplexObject]
+impl ImportJob {
+    /// Share of the cues imported so far, from 0 to 1.
+    async fn progress(&self) -> f64 {
+        match self.status {
+            JobStatus::Succeeded => 1.0,
+            _ if self.total == 0 => 0.0,
+            _ => self.processed as f64 / self.total as f64,
+        }
+    }
+
+    /// Problems found while parsing the file, with the line they were found on.
+    async fn report<'ctx>(&self, ctx: &Context<'ctx>) -> Result<ParseReport, Error> {
+        let pool = ctx.data::<Pool<Postgres>>()?;
+        let issues = sqlx::query!(
+            "SELECT line, message, error FROM import_job_issue WHERE job_id = $1 ORDER BY id;",
+            self.id
+        )
+        .fetch_all(pool)
+        .await?;
+
+        let mut report = ParseReport::default();
+        for issue in issues {
+            let parse_issue = ParseIssue {
+                line: issue.line as usize,
+                message: issue.message,
+            };
+            match issue.error {
+                true => report.errors.push(parse_issue),
+                false => report.warnings.push(parse_issue),
+            }
+        }
+        Ok(report)
+    }
+
+    /// The track the subtitles are imported into, once it exists.
+    async fn track<'ctx>(&self, ctx: &Context<'ctx>) -> Result<Option<Track>, Error> {
+        let pool = ctx.data::<Pool<Postgres>>()?;
+        match self.track_id {
+            Some(track_id) => {
+                let track: Track = sqlx::query_as!(
+                    Track,
+                    "SELECT id, language, kind as \"kind: TrackKind\", source_file, movie_id FROM track WHERE id = $1;",
+                    track_id
+                )
+                .fetch_one(pool)
+                .await?;
+                Ok(Some(track))
+            }
+            None => Ok(None),
+        }
+    }
+
+    async fn movie<'ctx>(&self, ctx: &Context<'ctx>) -> Result<Movie, Error> {
+        let pool = ctx.data::<Pool<Postgres>>()?;
+        let movie: Movie =
+            sqlx::query_as!(Movie, "SELECT * FROM movie WHERE id = $1;", self.movie_id)
+                .fetch_one(pool)
+                .await?;
+        Ok(movie)
+    }
+}
+
+// SQLx and async-graphql implementations for ImportJobQuery
+
+#[derive(Default)]
+pub struct ImportJobQuery;
+
+#[Object]
+impl ImportJobQuery {
+    async fn import_jobs(&self, ctx: &Context<'_>, movie_id: i64) -> Result<Vec<ImportJob>, Error> {
+        let pool = ctx.data::<Pool<Postgres>>()?;
+        let jobs: Vec<ImportJob> = sqlx::query_as!(
+            ImportJob,
+            "SELECT id, file_name, status as \"status: JobStatus\", processed, total, error, movie_id, track_id \
+            FROM import_job WHERE movie_id = $1 ORDER BY id;",
+            movie_id
+        )
+        .fetch_all(pool)
+        .await?;
+        Ok(jobs)
+    }
+
+    async fn import_job(&self, ctx: &Context<'_>, id: i64) -> Result<ImportJob, Error> {
+        let pool = ctx.data::<Pool<Postgres>>()?;
+        let job = load(pool, id).await?;
+        Ok(job)
+    }
+}
+
+#[derive(Default)]
+pub struct ImportJobSubscription;
+
+#[Subscription]
+impl ImportJobSubscription {
+    /// The state of an import job, first as it is and then on every change until it has
+    /// succeeded or failed.
+    async fn import_job(
+        &self,
+        ctx: &Context<'_>,
+        id: i64,
+    ) -> Result<impl Stream<Item = ImportJob>, Error> {
+        let pool = ctx.data::<Pool<Postgres>>()?.clone();
+        // subscribe before loading, so no change gets lost in between
+        let receiver = ctx.data::<JobUpdates>()?.0.subscribe();
+        let job = load(&pool, id).await?;
+
+        Ok(stream::unfold(
+            (Next::Send(job), receiver, pool),
+            move |(next, mut receiver, pool)| async move {
+                let job = match next {
+                    Next::Done => return None,
+                    Next::Send(job) => job,
+                    Next::Wait => loop {
+                        match receiver.recv().await {
+                            Ok(job) if job.id == id => break job,
+                            Ok(_) => continue,
+                            // missed changes, only the latest state matters anyway
+                            Err(RecvError::Lagged(_)) => break load(&pool, id).await.ok()?,
+                            Err(RecvError::Closed) => return None,
+                        }
+                    },
+                };
+                let next = if job.finished() {
+                    Next::Done
+                } else {
+                    Next::Wait
+                };
+                Some((job, (next, receiver, pool)))
+            },
+        ))
+    }
+}
+
+// what the import job subscription does next
+enum Next {
+    Send(ImportJob),
+    Wait,
+    Done,
+}
+
+pub async fn load(pool: &Pool<Postgres>, id: i64) -> Result<ImportJob, sqlx::Error> {
+    sqlx::query_as!(
+        ImportJob,
+        "SELECT id, file_name, status as \"status: JobStatus\", processed, total, error, movie_id, track_id \
+        FROM import_job WHERE id = $1;",
+        id
+    )
+    .fetch_one(pool)
+    .await
+}
+
+pub async fn create(
+    pool: &Pool<Postgres>,
+    movie_id: i64,
+    file_name: &str,
+) -> Result<ImportJob, sqlx::Error> {
+    sqlx::query_as!(
+        ImportJob,
+        "INSERT INTO import_job (movie_id, file_name) VALUES ($1, $2) \
+        RETURNING id, file_name, status as \"status: JobStatus\", processed, total, error, movie_id, track_id;",
+        movie_id,
+        file_name
+    )
+    .fetch_one(pool)
+    .await
+}
+
+/// Fails the jobs a previous run of the server left queued or running, they can't finish
+/// anymore. Returns how many there were.
+pub async fn fail_unfinished(pool: &Pool<Postgres>) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query!(
+        "UPDATE import_job SET status = 'failed', error = 'The server stopped before the import finished' \
+        WHERE status IN ('queued', 'running');"
+    )
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected())
+}
+
+/// Records how far an import job got and tells the subscriptions about it.
+pub struct JobProgress<'a> {
+    pub pool: &'a Pool<Postgres>,
+    pub updates: &'a JobUpdates,
+    pub job_id: i64,
+}
+
+impl JobProgress<'_> {
+    /// Marks the job as running, with the number of cues and the problems of its file.
+    pub async fn start(&self, total: usize, report: &ParseReport) -> Result<(), sqlx::Error> {
+        let issues = report
+            .warnings
+            .iter()
+            .map(|issue| (issue, false))
+            .chain(report.errors.iter().map(|issue| (issue, true)));
+        for (issue, error) in issues {
+            sqlx::query!(
+                "INSERT INTO import_job_issue (job_id, line, message, error) VALUES ($1, $2, $3, $4);",
+                self.job_id,
+                issue.line as i64,
+                issue.message,
+                error
+            )
+            .execute(self.pool)
+            .await?;
+        }
+
+        let job = sqlx::query_as!(
+            ImportJob,
+            "UPDATE import_job SET status = 'running', total = $1 WHERE id = $2 \
+            RETURNING id, file_name, status as \"status: JobStatus\", processed, total, error, movie_id, track_id;",
+            total as i64,
+            self.job_id
+        )
+        .fetch_one(self.pool)
+        .await?;
+        self.updates.send(&job);
+        Ok(())
+    }
+
+    pub async fn update(&self, processed: usize) -> Result<(), sqlx::Error> {
+        let job = sqlx::query_as!(
+            ImportJob,
+            "UPDATE import_job SET processed = $1 WHERE id = $2 \
+            RETURNING id, file_name, status as \"status: JobStatus\", processed, total, error, movie_id, track_id;",
+            processed as i64,
+            self.job_id
+        )
+        .fetch_one(self.pool)
+        .await?;
+        self.updates.send(&job);
+        Ok(())
+    }
+
+    /// Marks the job as succeeded with the track it imported into, or as failed.
+    pub async fn finish(&self, result: Result<i64, sqlx::Error>) -> Result<(), sqlx::Error> {
+        let job = match result {
+            Ok(track_id) => {
+                sqlx::query_as!(
+                    ImportJob,
+                    "UPDATE import_job SET status = 'succeeded', processed = total, track_id = $1 WHERE id = $2 \
+                    RETURNING id, file_name, status as \"status: JobStatus\", processed, total, error, movie_id, track_id;",
+                    track_id,
+                    self.job_id
+                )
+                .fetch_one(self.pool)
+                .await?
+            }
+            Err(error) => return self.fail(&error.to_string()).await,
+        };
+        self.updates.send(&job);
+        Ok(())
+    }
+
+    pub async fn fail(&self, error: &str) -> Result<(), sqlx::Error> {
+        let job = sqlx::query_as!(
+            ImportJob,
+            "UPDATE import_job SET status = 'failed', error = $1 WHERE id = $2 \
+            RETURNING id, file_name, status as \"status: JobStatus\", processed, total, error, movie_id, track_id;",
+            error,
+            self.job_id
+        )
+        .fetch_one(self.pool)
+        .await?;
+        self.updates.send(&job);
+        Ok(())
+    }
+}
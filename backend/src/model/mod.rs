@@ -1,20 +1,37 @@
 use async_graphql::*;
 
 use self::{
+    alignment::{AlignmentMutation, AlignmentQuery},
     character::{CharacterMutation, CharacterQuery},
     conversation::{ConversationMutation, ConversationQuery},
+    cornell::{CornellMutation, CornellQuery},
+    import::ImportMutation,
+    job::{ImportJobQuery, ImportJobSubscription},
     location::{LocationMutation, LocationQuery},
     movie::{MovieMutation, MovieQuery},
+    retime::RetimeMutation,
     scene::{SceneMutation, SceneQuery},
     sentence::{SentenceMutation, SentenceQuery},
+    sound_event::{SoundEventMutation, SoundEventQuery},
+    track::{TrackMutation, TrackQuery},
+    translation::TranslationMutation,
 };
 
-mod character;
+mod alignment;
+mod bulk;
+pub mod character;
 mod conversation;
-mod location;
+mod cornell;
+mod import;
+pub mod job;
+pub mod location;
 mod movie;
-mod scene;
-mod sentence;
+mod retime;
+pub mod scene;
+pub mod sentence;
+pub mod sound_event;
+pub mod track;
+mod translation;
 
 #[derive(MergedObject, Default)]
 pub struct QueryRoot(
@@ -24,6 +41,11 @@ pub struct QueryRoot(
     MovieQuery,
     SceneQuery,
     SentenceQuery,
+    SoundEventQuery,
+    TrackQuery,
+    AlignmentQuery,
+    ImportJobQuery,
+    CornellQuery,
 );
 
 #[derive(MergedObject, Default)]
@@ -34,4 +56,14 @@ pub struct MutationRoot(
     LocationMutation,
     SceneMutation,
     SentenceMutation,
+    SoundEventMutation,
+    TrackMutation,
+    ImportMutation,
+    AlignmentMutation,
+    RetimeMutation,
+    TranslationMutation,
+    CornellMutation,
 );
+
+#[derive(MergedSubscription, Default)]
+pub struct SubscriptionRoot(ImportJobSubscription);
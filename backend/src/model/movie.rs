@@ -6,6 +6,8 @@ use sqlx::{query_as, Pool, Postgres};
 pub struct Movie {
     pub id: i64,
     pub name: String,
+    /// The id the movie has in the corpus it was imported from, like "m0".
+    pub source_id: Option<String>,
 }
 
 #[derive(Default)]
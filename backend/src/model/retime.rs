@@ -0,0 +1,253 @@
+use async_graphql::*;
+use sqlx::{Pool, Postgres};
+
+use crate::parse::exact_framerate;
+
+/// Limits a retiming to part of a movie. Without a scope the whole movie is retimed.
+#[derive(Debug, Default, InputObject)]
+pub struct RetimeScope {
+    /// Only retime lines starting at or after this time in milliseconds.
+    pub from_ms: Option<i64>,
+    /// Only retime lines starting before this time in milliseconds.
+    pub to_ms: Option<i64>,
+    /// Only retime the sentences and sound events of this subtitle track.
+    pub track_id: Option<i64>,
+    /// Only retime the sentences of this scene, subtitles are in the scene of the screenplay
+    /// lines they were aligned with. Sound events have no scene and are left alone.
+    pub scene_id: Option<i64>,
+}
+
+/// A time in the subtitles and the time in the film it should be at.
+#[derive(Debug, InputObject)]
+pub struct SyncPoint {
+    pub subtitle_ms: i64,
+    pub film_ms: i64,
+}
+
+#[derive(Debug, SimpleObject)]
+pub struct RetimeResult {
+    pub sentence_count: u64,
+    pub sound_event_count: u64,
+}
+
+// async-graphql implementations for RetimeMutation
+
+#[derive(Default)]
+pub struct RetimeMutation;
+
+#[Object]
+impl RetimeMutation {
+    /// Shifts sentences and sound events by a number of milliseconds, which may be
+    /// negative.
+    async fn offset_subtitles(
+        &self,
+        ctx: &Context<'_>,
+        movie_id: i64,
+        offset_ms: i64,
+        scope: Option<RetimeScope>,
+    ) -> Result<RetimeResult, Error> {
+        let pool = ctx.data::<Pool<Postgres>>()?;
+        let result = retime(
+            pool,
+            movie_id,
+            &scope.unwrap_or_default(),
+            1.0,
+            offset_ms as f64,
+        )
+        .await?;
+        Ok(result)
+    }
+
+    /// Stretches the timing linearly so both sync points end up where they belong.
+    async fn stretch_subtitles(
+        &self,
+        ctx: &Context<'_>,
+        movie_id: i64,
+        first: SyncPoint,
+        second: SyncPoint,
+        scope: Option<RetimeScope>,
+    ) -> Result<RetimeResult, Error> {
+        let pool = ctx.data::<Pool<Postgres>>()?;
+        let scale = (second.film_ms - first.film_ms) as f64
+            / (second.subtitle_ms - first.subtitle_ms) as f64;
+        if !scale.is_finite() || scale <= 0.0 {
+            return Err(Error::new(
+                "Sync points must be in the same order in the subtitles and the film",
+            ));
+        }
+        let shift = first.film_ms as f64 - first.subtitle_ms as f64 * scale;
+
+        let result = retime(pool, movie_id, &scope.unwrap_or_default(), scale, shift).await?;
+        Ok(result)
+    }
+
+    /// Converts the timing of subtitles made for a release with a different framerate,
+    /// like 23.976 to 25 frames per second.
+    async fn convert_framerate(
+        &self,
+        ctx: &Context<'_>,
+        movie_id: i64,
+        from_fps: f64,
+        to_fps: f64,
+        scope: Option<RetimeScope>,
+    ) -> Result<RetimeResult, Error> {
+        let pool = ctx.data::<Pool<Postgres>>()?;
+        if from_fps <= 0.0 || to_fps <= 0.0 {
+            return Err(Error::new("Framerates must be positive"));
+        }
+        let scale = exact_framerate(from_fps) / exact_framerate(to_fps);
+
+        let result = retime(pool, movie_id, &scope.unwrap_or_default(), scale, 0.0).await?;
+        Ok(result)
+    }
+}
+
+// maps the start and end of every timed line in scope to `time * scale + shift`, in a
+// single transaction. Times that would become negative are clamped to zero.
+async fn retime(
+    pool: &Pool<Postgres>,
+    movie_id: i64,
+    scope: &RetimeScope,
+    scale: f64,
+    shift: f64,
+) -> Result<RetimeResult, sqlx::Error> {
+    let mut transaction = pool.begin().await?;
+    let sentences = sqlx::query!(
+        "UPDATE sentence SET \
+        start_ms = GREATEST(0, ROUND(start_ms * $2::DOUBLE PRECISION + $3::DOUBLE PRECISION))::BIGINT, \
+        end_ms = GREATEST(0, ROUND(end_ms * $2::DOUBLE PRECISION + $3::DOUBLE PRECISION))::BIGINT \
+        WHERE movie_id = $1 AND start_ms IS NOT NULL \
+        AND ($4::BIGINT IS NULL OR start_ms >= $4) \
+        AND ($5::BIGINT IS NULL OR start_ms < $5) \
+        AND ($6::BIGINT IS NULL OR scene_id = $6) \
+        AND ($7::BIGINT IS NULL OR track_id = $7);",
+        movie_id,
+        scale,
+        shift,
+        scope.from_ms,
+        scope.to_ms,
+        scope.scene_id,
+        scope.track_id
+    )
+    .execute(&mut transaction)
+    .await?;
+
+    let sound_events = match scope.scene_id {
+        Some(_) => 0,
+        None => sqlx::query!(
+            "UPDATE sound_event SET \
+            start_ms = GREATEST(0, ROUND(start_ms * $2::DOUBLE PRECISION + $3::DOUBLE PRECISION))::BIGINT, \
+            end_ms = GREATEST(0, ROUND(end_ms * $2::DOUBLE PRECISION + $3::DOUBLE PRECISION))::BIGINT \
+            WHERE movie_id = $1 \
+            AND ($4::BIGINT IS NULL OR start_ms >= $4) \
+            AND ($5::BIGINT IS NULL OR start_ms < $5) \
+            AND ($6::BIGINT IS NULL OR track_id = $6);",
+            movie_id,
+            scale,
+            shift,
+            scope.from_ms,
+            scope.to_ms,
+            scope.track_id
+        )
+        .execute(&mut transaction)
+        .await?
+        .rows_affected(),
+    };
+    transaction.commit().await?;
+
+    Ok(RetimeResult {
+        sentence_count: sentences.rows_affected(),
+        sound_event_count: sound_events,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::{MutationRoot, QueryRoot, SubscriptionRoot};
+
+    #[sqlx::test]
+    async fn retimes_the_subtitles_of_a_scene(pool: Pool<Postgres>) {
+        let movie_id =
+            sqlx::query_scalar!("INSERT INTO movie (name) VALUES ('Film') RETURNING id;")
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        let track_id = sqlx::query_scalar!(
+            "INSERT INTO track (movie_id, language, kind) VALUES ($1, 'en', 'original') RETURNING id;",
+            movie_id
+        )
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        let scene_id = sqlx::query_scalar!(
+            "INSERT INTO scene (name, movie_id) VALUES ('Hall', $1) RETURNING id;",
+            movie_id
+        )
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        let script_id = sqlx::query_scalar!(
+            "INSERT INTO sentence (movie_id, scene_id, text, position) \
+            VALUES ($1, $2, 'Where were you?', 0) RETURNING id;",
+            movie_id,
+            scene_id
+        )
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        let subtitle_ids = sqlx::query_scalar!(
+            "INSERT INTO sentence (movie_id, track_id, text, start_ms, end_ms, position) \
+            VALUES ($1, $2, 'Before the scene.', 0, 1000, 0), ($1, $2, 'Where were you?', 2000, 3000, 1) \
+            RETURNING id;",
+            movie_id,
+            track_id
+        )
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+        sqlx::query!(
+            "INSERT INTO script_alignment (script_sentence_id, subtitle_sentence_id, confidence, confirmed, movie_id) \
+            VALUES ($1, $2, 1, true, $3);",
+            script_id,
+            subtitle_ids[1],
+            movie_id
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        let schema = Schema::build(
+            QueryRoot::default(),
+            MutationRoot::default(),
+            SubscriptionRoot::default(),
+        )
+        .data(pool.clone())
+        .finish();
+        let response = schema
+            .execute(format!(
+                "mutation {{ applyScriptAlignments(movieId: {}) {{ id }} }}",
+                movie_id
+            ))
+            .await;
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+
+        let scope = RetimeScope {
+            track_id: Some(track_id),
+            scene_id: Some(scene_id),
+            ..RetimeScope::default()
+        };
+        let result = retime(&pool, movie_id, &scope, 1.0, 500.0).await.unwrap();
+        assert_eq!(result.sentence_count, 1);
+
+        let times = sqlx::query!(
+            "SELECT start_ms AS \"start_ms!\", end_ms AS \"end_ms!\" FROM sentence \
+            WHERE track_id = $1 ORDER BY position;",
+            track_id
+        )
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+        let times: Vec<(i64, i64)> = times.iter().map(|row| (row.start_ms, row.end_ms)).collect();
+        assert_eq!(times, vec![(0, 1000), (2500, 3500)]);
+    }
+}
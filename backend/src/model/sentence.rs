@@ -2,51 +2,138 @@ use async_graphql::*;
 use serde::{Deserialize, Serialize};
 use sqlx::{Pool, Postgres};
 
-use super::{character::Character, conversation::Conversation, movie::Movie};
+use crate::parse::markup::{Markup, MarkupKind};
+
+use super::{
+    character::Character,
+    conversation::Conversation,
+    movie::Movie,
+    scene::Scene,
+    track::{Track, TrackKind},
+};
 
 // given the context given in the above comemnts write the struct for sentence
-#[derive(Debug, Serialize, Deserialize, SimpleObject)]
+#[derive(Debug, Clone, Serialize, Deserialize, SimpleObject)]
 #[graphql(complex)]
 pub struct Sentence {
     pub id: i64,
     pub text: String,
-    pub start_time: i64,
-    pub end_time: i64,
+    /// Start of the sentence in milliseconds from the beginning of the movie, unknown for
+    /// sentences from a screenplay that isn't aligned yet.
+    pub start_ms: Option<i64>,
+    /// End of the sentence in milliseconds from the beginning of the movie.
+    pub end_ms: Option<i64>,
     pub position: i64,
+    /// The style name the sentence had in the subtitle file it was imported from.
+    pub style: Option<String>,
+    /// How the sentence is spoken according to the screenplay, like "whispering".
+    pub parenthetical: Option<String>,
     #[graphql(skip)]
     pub speaker_id: Option<i64>,
     #[graphql(skip)]
     pub conversation_id: Option<i64>,
     #[graphql(skip)]
+    pub scene_id: Option<i64>,
+    #[graphql(skip)]
     pub movie_id: i64,
+    #[graphql(skip)]
+    pub track_id: Option<i64>,
+    /// The id the sentence has in the corpus it was imported from, like "L194".
+    pub source_id: Option<String>,
 }
 
 // SQLx and async-graphql implementations for Sentence
 
 #[ComplexObject]
 impl Sentence {
-    async fn speaker<'ctx>(&self, ctx: &Context<'ctx>) -> Result<Character, Error> {
+    async fn speaker<'ctx>(&self, ctx: &Context<'ctx>) -> Result<Option<Character>, Error> {
         let pool = ctx.data::<Pool<Postgres>>()?;
-        let speaker: Character = sqlx::query_as!(
-            Character,
-            "SELECT * FROM character WHERE id = $1;",
-            self.speaker_id
-        )
-        .fetch_one(pool)
-        .await?;
-        Ok(speaker)
+        match self.speaker_id {
+            Some(speaker_id) => {
+                let speaker: Character = sqlx::query_as!(
+                    Character,
+                    "SELECT * FROM character WHERE id = $1;",
+                    speaker_id
+                )
+                .fetch_one(pool)
+                .await?;
+                Ok(Some(speaker))
+            }
+            None => Ok(None),
+        }
+    }
+
+    async fn conversation<'ctx>(&self, ctx: &Context<'ctx>) -> Result<Option<Conversation>, Error> {
+        let pool = ctx.data::<Pool<Postgres>>()?;
+        match self.conversation_id {
+            Some(conversation_id) => {
+                let conversation: Conversation = sqlx::query_as!(
+                    Conversation,
+                    "SELECT * FROM conversation WHERE id = $1;",
+                    conversation_id
+                )
+                .fetch_one(pool)
+                .await?;
+                Ok(Some(conversation))
+            }
+            None => Ok(None),
+        }
+    }
+
+    async fn scene<'ctx>(&self, ctx: &Context<'ctx>) -> Result<Option<Scene>, Error> {
+        let pool = ctx.data::<Pool<Postgres>>()?;
+        match self.scene_id {
+            Some(scene_id) => {
+                let scene: Scene =
+                    sqlx::query_as!(Scene, "SELECT * FROM scene WHERE id = $1;", scene_id)
+                        .fetch_one(pool)
+                        .await?;
+                Ok(Some(scene))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// The subtitle track the sentence belongs to, none for dialogue from a screenplay.
+    async fn track<'ctx>(&self, ctx: &Context<'ctx>) -> Result<Option<Track>, Error> {
+        let pool = ctx.data::<Pool<Postgres>>()?;
+        match self.track_id {
+            Some(track_id) => {
+                let track: Track = sqlx::query_as!(
+                    Track,
+                    "SELECT id, language, kind as \"kind: TrackKind\", source_file, movie_id FROM track WHERE id = $1;",
+                    track_id
+                )
+                .fetch_one(pool)
+                .await?;
+                Ok(Some(track))
+            }
+            None => Ok(None),
+        }
     }
 
-    async fn conversation<'ctx>(&self, ctx: &Context<'ctx>) -> Result<Conversation, Error> {
+    /// The sentences of tracks in another language that are shown at the same time, once
+    /// the tracks are aligned.
+    async fn translations<'ctx>(
+        &self,
+        ctx: &Context<'ctx>,
+        #[graphql(desc = "Language tag of the tracks, like \"en\"")] language: String,
+    ) -> Result<Vec<Sentence>, Error> {
         let pool = ctx.data::<Pool<Postgres>>()?;
-        let conversation: Conversation = sqlx::query_as!(
-            Conversation,
-            "SELECT * FROM conversation WHERE id = $1;",
-            self.conversation_id
+        let translations: Vec<Sentence> = sqlx::query_as!(
+            Sentence,
+            "SELECT s.* FROM sentence as s \
+            INNER JOIN track as t ON t.id = s.track_id \
+            INNER JOIN sentence_translation as st \
+            ON st.sentence_id = $1 AND st.translation_id = s.id \
+            OR st.translation_id = $1 AND st.sentence_id = s.id \
+            WHERE t.language = $2 ORDER BY s.start_ms, s.position;",
+            self.id,
+            language
         )
-        .fetch_one(pool)
+        .fetch_all(pool)
         .await?;
-        Ok(conversation)
+        Ok(translations)
     }
 
     async fn directed_to<'ctx>(&self, ctx: &Context<'ctx>) -> Result<Vec<Character>, Error> {
@@ -63,6 +150,20 @@ impl Sentence {
         Ok(directed_to)
     }
 
+    /// Formatting of the text like italics or on screen position, ordered by start.
+    async fn markup<'ctx>(&self, ctx: &Context<'ctx>) -> Result<Vec<Markup>, Error> {
+        let pool = ctx.data::<Pool<Postgres>>()?;
+        let markup: Vec<Markup> = sqlx::query_as!(
+            Markup,
+            "SELECT kind as \"kind: MarkupKind\", start_char, end_char, value FROM sentence_markup \
+            WHERE sentence_id = $1 ORDER BY start_char, end_char;",
+            self.id
+        )
+        .fetch_all(pool)
+        .await?;
+        Ok(markup)
+    }
+
     async fn movie<'ctx>(&self, ctx: &Context<'ctx>) -> Result<Movie, Error> {
         let pool = ctx.data::<Pool<Postgres>>()?;
         let movie: Movie =
@@ -80,12 +181,19 @@ pub struct SentenceQuery;
 
 #[Object]
 impl SentenceQuery {
-    async fn sentences(&self, ctx: &Context<'_>, movie_id: i64) -> Result<Vec<Sentence>, Error> {
+    async fn sentences(
+        &self,
+        ctx: &Context<'_>,
+        movie_id: i64,
+        #[graphql(desc = "Only sentences of this subtitle track")] track_id: Option<i64>,
+    ) -> Result<Vec<Sentence>, Error> {
         let pool = ctx.data::<Pool<Postgres>>()?;
         let sentences: Vec<Sentence> = sqlx::query_as!(
             Sentence,
-            "SELECT * FROM sentence WHERE movie_id = $1;",
-            movie_id
+            "SELECT * FROM sentence WHERE movie_id = $1 AND ($2::BIGINT IS NULL OR track_id = $2) \
+            ORDER BY position, id;",
+            movie_id,
+            track_id
         )
         .fetch_all(pool)
         .await?;
@@ -0,0 +1,125 @@
+use async_graphql::*;
+use serde::{Deserialize, Serialize};
+use sqlx::{Pool, Postgres};
+
+use super::{
+    movie::Movie,
+    track::{Track, TrackKind},
+};
+
+/// A non-dialogue annotation of a subtitle file, like "door slams" or "laughs".
+#[derive(Debug, Serialize, Deserialize, SimpleObject)]
+#[graphql(complex)]
+pub struct SoundEvent {
+    pub id: i64,
+    pub text: String,
+    /// Start of the event in milliseconds from the beginning of the movie.
+    pub start_ms: i64,
+    /// End of the event in milliseconds from the beginning of the movie.
+    pub end_ms: i64,
+    pub position: i64,
+    #[graphql(skip)]
+    pub movie_id: i64,
+    #[graphql(skip)]
+    pub track_id: i64,
+}
+
+// SQLx and async-graphql implementations for SoundEvent
+
+#[ComplexObject]
+impl SoundEvent {
+    async fn movie<'ctx>(&self, ctx: &Context<'ctx>) -> Result<Movie, Error> {
+        let pool = ctx.data::<Pool<Postgres>>()?;
+        let movie: Movie =
+            sqlx::query_as!(Movie, "SELECT * FROM movie WHERE id = $1;", self.movie_id)
+                .fetch_one(pool)
+                .await?;
+        Ok(movie)
+    }
+
+    async fn track<'ctx>(&self, ctx: &Context<'ctx>) -> Result<Track, Error> {
+        let pool = ctx.data::<Pool<Postgres>>()?;
+        let track: Track = sqlx::query_as!(
+            Track,
+            "SELECT id, language, kind as \"kind: TrackKind\", source_file, movie_id FROM track WHERE id = $1;",
+            self.track_id
+        )
+        .fetch_one(pool)
+        .await?;
+        Ok(track)
+    }
+}
+
+// SQLx and async-graphql implementations for SoundEventQuery
+
+#[derive(Default)]
+pub struct SoundEventQuery;
+
+#[Object]
+impl SoundEventQuery {
+    async fn sound_events(
+        &self,
+        ctx: &Context<'_>,
+        movie_id: i64,
+        #[graphql(desc = "Only events of this subtitle track")] track_id: Option<i64>,
+        #[graphql(desc = "Only events containing this text, ignoring case")] search: Option<String>,
+    ) -> Result<Vec<SoundEvent>, Error> {
+        let pool = ctx.data::<Pool<Postgres>>()?;
+        let sound_events: Vec<SoundEvent> = sqlx::query_as!(
+            SoundEvent,
+            "SELECT * FROM sound_event WHERE movie_id = $1 AND ($2::BIGINT IS NULL OR track_id = $2) \
+            AND ($3::TEXT IS NULL OR text ILIKE '%' || $3 || '%') ORDER BY track_id, position;",
+            movie_id,
+            track_id,
+            search
+        )
+        .fetch_all(pool)
+        .await?;
+        Ok(sound_events)
+    }
+
+    async fn sound_event(&self, ctx: &Context<'_>, id: i64) -> Result<SoundEvent, Error> {
+        let pool = ctx.data::<Pool<Postgres>>()?;
+        let sound_event: SoundEvent =
+            sqlx::query_as!(SoundEvent, "SELECT * FROM sound_event WHERE id = $1;", id)
+                .fetch_one(pool)
+                .await?;
+        Ok(sound_event)
+    }
+}
+
+#[derive(Default)]
+pub struct SoundEventMutation;
+
+#[Object]
+impl SoundEventMutation {
+    async fn update_sound_event(
+        &self,
+        ctx: &Context<'_>,
+        id: i64,
+        text: String,
+    ) -> Result<SoundEvent, Error> {
+        let pool = ctx.data::<Pool<Postgres>>()?;
+        let sound_event: SoundEvent = sqlx::query_as!(
+            SoundEvent,
+            "UPDATE sound_event SET text = $1 WHERE id = $2 RETURNING *;",
+            text,
+            id
+        )
+        .fetch_one(pool)
+        .await?;
+        Ok(sound_event)
+    }
+
+    async fn delete_sound_event(&self, ctx: &Context<'_>, id: i64) -> Result<SoundEvent, Error> {
+        let pool = ctx.data::<Pool<Postgres>>()?;
+        let sound_event: SoundEvent = sqlx::query_as!(
+            SoundEvent,
+            "DELETE FROM sound_event WHERE id = $1 RETURNING *;",
+            id
+        )
+        .fetch_one(pool)
+        .await?;
+        Ok(sound_event)
+    }
+}
@@ -0,0 +1,184 @@
+use async_graphql::*;
+use serde::{Deserialize, Serialize};
+use sqlx::{Pool, Postgres};
+
+use super::{movie::Movie, sentence::Sentence, sound_event::SoundEvent};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Enum, sqlx::Type)]
+#[sqlx(type_name = "track_kind", rename_all = "lowercase")]
+pub enum TrackKind {
+    /// Subtitles in the language the movie was made in.
+    Original,
+    /// Subtitles translated into another language.
+    Translation,
+    /// Subtitles for the deaf and hard of hearing, with sound events and speaker labels.
+    Sdh,
+}
+
+/// One set of subtitles of a movie in one language.
+#[derive(Debug, Serialize, Deserialize, SimpleObject)]
+#[graphql(complex)]
+pub struct Track {
+    pub id: i64,
+    /// BCP 47 language tag like "de" or "en-US", "und" if it isn't known.
+    pub language: String,
+    pub kind: TrackKind,
+    /// Name of the file the subtitles were imported from.
+    pub source_file: Option<String>,
+    #[graphql(skip)]
+    pub movie_id: i64,
+}
+
+// SQLx and async-graphql implementations for Track
+
+#[ComplexObject]
+impl Track {
+    async fn movie<'ctx>(&self, ctx: &Context<'ctx>) -> Result<Movie, Error> {
+        let pool = ctx.data::<Pool<Postgres>>()?;
+        let movie: Movie =
+            sqlx::query_as!(Movie, "SELECT * FROM movie WHERE id = $1;", self.movie_id)
+                .fetch_one(pool)
+                .await?;
+        Ok(movie)
+    }
+
+    async fn sentences<'ctx>(&self, ctx: &Context<'ctx>) -> Result<Vec<Sentence>, Error> {
+        let pool = ctx.data::<Pool<Postgres>>()?;
+        let sentences: Vec<Sentence> = sqlx::query_as!(
+            Sentence,
+            "SELECT * FROM sentence WHERE track_id = $1 ORDER BY position;",
+            self.id
+        )
+        .fetch_all(pool)
+        .await?;
+        Ok(sentences)
+    }
+
+    async fn sound_events<'ctx>(&self, ctx: &Context<'ctx>) -> Result<Vec<SoundEvent>, Error> {
+        let pool = ctx.data::<Pool<Postgres>>()?;
+        let sound_events: Vec<SoundEvent> = sqlx::query_as!(
+            SoundEvent,
+            "SELECT * FROM sound_event WHERE track_id = $1 ORDER BY position;",
+            self.id
+        )
+        .fetch_all(pool)
+        .await?;
+        Ok(sound_events)
+    }
+}
+
+// SQLx and async-graphql implementations for TrackQuery
+
+#[derive(Default)]
+pub struct TrackQuery;
+
+#[Object]
+impl TrackQuery {
+    async fn tracks(
+        &self,
+        ctx: &Context<'_>,
+        movie_id: i64,
+        language: Option<String>,
+    ) -> Result<Vec<Track>, Error> {
+        let pool = ctx.data::<Pool<Postgres>>()?;
+        let tracks: Vec<Track> = sqlx::query_as!(
+            Track,
+            "SELECT id, language, kind as \"kind: TrackKind\", source_file, movie_id FROM track \
+            WHERE movie_id = $1 AND ($2::TEXT IS NULL OR language = $2) ORDER BY id;",
+            movie_id,
+            language
+        )
+        .fetch_all(pool)
+        .await?;
+        Ok(tracks)
+    }
+
+    async fn track(&self, ctx: &Context<'_>, id: i64) -> Result<Track, Error> {
+        let pool = ctx.data::<Pool<Postgres>>()?;
+        let track: Track = sqlx::query_as!(
+            Track,
+            "SELECT id, language, kind as \"kind: TrackKind\", source_file, movie_id FROM track WHERE id = $1;",
+            id
+        )
+        .fetch_one(pool)
+        .await?;
+        Ok(track)
+    }
+}
+
+#[derive(Default)]
+pub struct TrackMutation;
+
+#[Object]
+impl TrackMutation {
+    async fn create_track(
+        &self,
+        ctx: &Context<'_>,
+        movie_id: i64,
+        language: String,
+        kind: TrackKind,
+        source_file: Option<String>,
+    ) -> Result<Track, Error> {
+        let pool = ctx.data::<Pool<Postgres>>()?;
+        let track: Track = sqlx::query_as!(
+            Track,
+            "INSERT INTO track (movie_id, language, kind, source_file) VALUES ($1, $2, $3, $4) \
+            RETURNING id, language, kind as \"kind: TrackKind\", source_file, movie_id;",
+            movie_id,
+            language,
+            kind as TrackKind,
+            source_file
+        )
+        .fetch_one(pool)
+        .await?;
+        Ok(track)
+    }
+
+    async fn update_track(
+        &self,
+        ctx: &Context<'_>,
+        id: i64,
+        language: Option<String>,
+        kind: Option<TrackKind>,
+        source_file: Option<String>,
+    ) -> Result<Track, Error> {
+        let pool = ctx.data::<Pool<Postgres>>()?;
+        let track: Track = sqlx::query_as!(
+            Track,
+            "UPDATE track SET language = COALESCE($1, language), kind = COALESCE($2, kind), source_file = COALESCE($3, source_file) WHERE id = $4 \
+            RETURNING id, language, kind as \"kind: TrackKind\", source_file, movie_id;",
+            language,
+            kind as Option<TrackKind>,
+            source_file,
+            id
+        )
+        .fetch_one(pool)
+        .await?;
+        Ok(track)
+    }
+
+    /// Deletes a track together with its sentences and sound events.
+    async fn delete_track(&self, ctx: &Context<'_>, id: i64) -> Result<Track, Error> {
+        let pool = ctx.data::<Pool<Postgres>>()?;
+        let mut transaction = pool.begin().await?;
+
+        // markup, addressees, alignments and links go with their sentences
+        sqlx::query!("DELETE FROM sentence WHERE track_id = $1;", id)
+            .execute(&mut transaction)
+            .await?;
+        sqlx::query!("DELETE FROM sound_event WHERE track_id = $1;", id)
+            .execute(&mut transaction)
+            .await?;
+        let track: Track = sqlx::query_as!(
+            Track,
+            "DELETE FROM track WHERE id = $1 \
+            RETURNING id, language, kind as \"kind: TrackKind\", source_file, movie_id;",
+            id
+        )
+        .fetch_one(&mut transaction)
+        .await?;
+
+        transaction.commit().await?;
+        Ok(track)
+    }
+}
@@ -0,0 +1,156 @@
+use async_graphql::*;
+use serde::{Deserialize, Serialize};
+use sqlx::{Pool, Postgres};
+
+use crate::align::{translation, Timed};
+
+use super::sentence::Sentence;
+
+/// A link between a sentence and its counterpart in another subtitle track.
+#[derive(Debug, Serialize, Deserialize, SimpleObject)]
+#[graphql(complex)]
+pub struct SentenceTranslation {
+    pub sentence_id: i64,
+    pub translation_id: i64,
+}
+
+// SQLx and async-graphql implementations for SentenceTranslation
+
+#[ComplexObject]
+impl SentenceTranslation {
+    async fn sentence<'ctx>(&self, ctx: &Context<'ctx>) -> Result<Sentence, Error> {
+        let pool = ctx.data::<Pool<Postgres>>()?;
+        let sentence: Sentence = sqlx::query_as!(
+            Sentence,
+            "SELECT * FROM sentence WHERE id = $1;",
+            self.sentence_id
+        )
+        .fetch_one(pool)
+        .await?;
+        Ok(sentence)
+    }
+
+    async fn translation<'ctx>(&self, ctx: &Context<'ctx>) -> Result<Sentence, Error> {
+        let pool = ctx.data::<Pool<Postgres>>()?;
+        let sentence: Sentence = sqlx::query_as!(
+            Sentence,
+            "SELECT * FROM sentence WHERE id = $1;",
+            self.translation_id
+        )
+        .fetch_one(pool)
+        .await?;
+        Ok(sentence)
+    }
+}
+
+#[derive(Default)]
+pub struct TranslationMutation;
+
+#[Object]
+impl TranslationMutation {
+    /// Links the sentences of two subtitle tracks that are shown at the same time. A
+    /// sentence can be linked to several sentences of the other track. Links made by an
+    /// earlier run between the same tracks are replaced.
+    async fn align_tracks(
+        &self,
+        ctx: &Context<'_>,
+        track_id: i64,
+        translation_track_id: i64,
+        #[graphql(
+            desc = "How much of the shorter of two sentences has to overlap the other one to link them, from 0 to 1",
+            default = 0.5
+        )]
+        min_overlap: f64,
+    ) -> Result<Vec<SentenceTranslation>, Error> {
+        let pool = ctx.data::<Pool<Postgres>>()?;
+        let mut transaction = pool.begin().await?;
+
+        sqlx::query!(
+            "DELETE FROM sentence_translation as st USING sentence as a, sentence as b \
+            WHERE a.id = st.sentence_id AND b.id = st.translation_id \
+            AND (a.track_id = $1 AND b.track_id = $2 OR a.track_id = $2 AND b.track_id = $1);",
+            track_id,
+            translation_track_id
+        )
+        .execute(&mut transaction)
+        .await?;
+
+        let sentences = sqlx::query_as!(
+            Sentence,
+            "SELECT * FROM sentence WHERE track_id = $1 ORDER BY start_ms, position;",
+            track_id
+        )
+        .fetch_all(&mut transaction)
+        .await?;
+        let translations = sqlx::query_as!(
+            Sentence,
+            "SELECT * FROM sentence WHERE track_id = $1 ORDER BY start_ms, position;",
+            translation_track_id
+        )
+        .fetch_all(&mut transaction)
+        .await?;
+
+        let timed = |sentence: &'_ Sentence| Timed {
+            start: sentence.start_ms.unwrap_or_default(),
+            end: sentence.end_ms.unwrap_or_default(),
+            text: "",
+        };
+        let source: Vec<Timed> = sentences.iter().map(timed).collect();
+        let target: Vec<Timed> = translations.iter().map(timed).collect();
+
+        let links: Vec<SentenceTranslation> = translation::align(&source, &target, min_overlap)
+            .into_iter()
+            .map(|(i, j)| SentenceTranslation {
+                sentence_id: sentences[i].id,
+                translation_id: translations[j].id,
+            })
+            .collect();
+        let sentence_ids: Vec<i64> = links.iter().map(|link| link.sentence_id).collect();
+        let translation_ids: Vec<i64> = links.iter().map(|link| link.translation_id).collect();
+        sqlx::query!(
+            "INSERT INTO sentence_translation (sentence_id, translation_id) \
+            SELECT * FROM UNNEST($1::BIGINT[], $2::BIGINT[]);",
+            &sentence_ids,
+            &translation_ids
+        )
+        .execute(&mut transaction)
+        .await?;
+
+        transaction.commit().await?;
+        Ok(links)
+    }
+
+    /// Copies the speakers of one track's sentences onto the sentences linked to them in
+    /// another track. Sentences linked to lines of different speakers are left alone.
+    async fn copy_speakers(
+        &self,
+        ctx: &Context<'_>,
+        from_track_id: i64,
+        to_track_id: i64,
+        #[graphql(desc = "Also replace speakers that are already set", default = false)]
+        overwrite: bool,
+    ) -> Result<Vec<Sentence>, Error> {
+        let pool = ctx.data::<Pool<Postgres>>()?;
+        let sentences: Vec<Sentence> = sqlx::query_as!(
+            Sentence,
+            "WITH linked AS ( \
+                SELECT st.translation_id as id, st.sentence_id as from_id FROM sentence_translation as st \
+                UNION SELECT st.sentence_id, st.translation_id FROM sentence_translation as st \
+            ), speakers AS ( \
+                SELECT linked.id, MIN(f.speaker_id) as speaker_id FROM linked \
+                INNER JOIN sentence as f ON f.id = linked.from_id \
+                WHERE f.track_id = $1 AND f.speaker_id IS NOT NULL \
+                GROUP BY linked.id HAVING COUNT(DISTINCT f.speaker_id) = 1 \
+            ) \
+            UPDATE sentence SET speaker_id = speakers.speaker_id FROM speakers \
+            WHERE sentence.id = speakers.id AND sentence.track_id = $2 \
+            AND (sentence.speaker_id IS NULL OR $3) RETURNING sentence.*;",
+            from_track_id,
+            to_track_id,
+            overwrite
+        )
+        .fetch_all(pool)
+        .await?;
+        Ok(sentences)
+    }
+}
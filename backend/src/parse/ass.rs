@@ -0,0 +1,207 @@
+use super::{parse_timestamp, ParseReport, Sub, SubKind};
+
+// the v4+ event format, used when a file has no Format line in its events section
+const DEFAULT_FORMAT: [&str; 10] = [
+    "layer", "start", "end", "style", "name", "marginl", "marginr", "marginv", "effect", "text",
+];
+
+/// Parses a decoded Advanced SubStation (.ass) or SubStation Alpha (.ssa) file.
+///
+/// Only `Dialogue` events are read. The `Name` field becomes the speaker and the style
+/// name is kept on the cue. Override tags like `{\i1}` are turned into markup later on.
+pub fn parse(text: &str) -> (Vec<Sub>, ParseReport) {
+    let mut report = ParseReport::default();
+
+    let text = text.trim_start_matches('\u{feff}');
+    let text = text.replace("\r\n", "\n").replace('\r', "\n");
+
+    let mut in_events = false;
+    let mut format: Vec<String> = DEFAULT_FORMAT
+        .iter()
+        .map(|field| field.to_string())
+        .collect();
+    let mut subs = Vec::new();
+
+    for (i, raw) in text.split('\n').enumerate() {
+        let line = i + 1;
+        let raw = raw.trim();
+
+        if raw.starts_with('[') && raw.ends_with(']') {
+            in_events = raw.eq_ignore_ascii_case("[events]");
+            continue;
+        }
+        if !in_events {
+            continue;
+        }
+
+        let (kind, value) = match raw.split_once(':') {
+            Some((kind, value)) => (kind.trim(), value.trim_start()),
+            None => continue,
+        };
+
+        match kind {
+            "Format" => {
+                format = value
+                    .split(',')
+                    .map(|field| field.trim().to_ascii_lowercase())
+                    .collect();
+                if !format.iter().any(|field| field == "text") {
+                    report.error(line, "event format has no Text field");
+                }
+            }
+            "Dialogue" => match parse_dialogue(&format, value) {
+                Some(sub) => {
+                    let sub = Sub { line, ..sub };
+                    if sub.end < sub.start {
+                        report.warning(line, "event ends before it starts, end set to start");
+                        subs.push(Sub {
+                            end: sub.start,
+                            ..sub
+                        });
+                    } else if sub.text.is_empty() {
+                        report.warning(line, "event has no text, skipped");
+                    } else {
+                        subs.push(sub);
+                    }
+                }
+                None => report.error(line, "invalid dialogue event, skipped"),
+            },
+            _ => {}
+        }
+    }
+
+    if subs.is_empty() && report.errors.is_empty() {
+        report.error(1, "no dialogue events found");
+    }
+
+    // events don't have to be in order in the file
+    subs.sort_by_key(|sub| sub.start);
+
+    (subs, report)
+}
+
+fn parse_dialogue(format: &[String], value: &str) -> Option<Sub> {
+    // the text is always the last field and may contain commas itself
+    let fields: Vec<&str> = value.splitn(format.len(), ',').collect();
+    if fields.len() != format.len() {
+        return None;
+    }
+    let field = |name: &str| {
+        format
+            .iter()
+            .position(|field| field == name)
+            .map(|i| fields[i].trim())
+    };
+
+    let start = parse_timestamp(field("start")?)?;
+    let end = parse_timestamp(field("end")?)?;
+    let non_empty = |value: Option<&str>| value.filter(|value| !value.is_empty()).map(String::from);
+
+    Some(Sub {
+        kind: SubKind::Dialogue,
+        index: None,
+        line: 0,
+        start,
+        end,
+        text: clean_text(field("text")?),
+        speaker: non_empty(field("name")),
+        style: non_empty(field("style").map(|style| style.trim_start_matches('*'))),
+        markup: Vec::new(),
+    })
+}
+
+// turns the \N and \n escapes into line breaks and \h into a space and drops vector
+// drawings between {\p1} and {\p0}. Override blocks like {\i1} are left for the markup.
+fn clean_text(text: &str) -> String {
+    let mut plain = String::with_capacity(text.len());
+    let mut drawing = false;
+    let mut rest = text;
+
+    while !rest.is_empty() {
+        if let Some(block) = rest.strip_prefix('{') {
+            let close = block.find('}').unwrap_or(block.len());
+            match drawing_scale(&block[..close]) {
+                Some(scale) => drawing = scale != 0,
+                None => plain.push_str(&rest[..(close + 2).min(rest.len())]),
+            }
+            rest = block.get(close + 1..).unwrap_or_default();
+            continue;
+        }
+
+        let next = rest.find('{').unwrap_or(rest.len());
+        if !drawing {
+            plain.push_str(&rest[..next]);
+        }
+        rest = &rest[next..];
+    }
+
+    plain
+        .replace("\\N", "\n")
+        .replace("\\n", "\n")
+        .replace("\\h", " ")
+        .lines()
+        .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+// the scale of the last \p tag in an override block, if there is one
+fn drawing_scale(block: &str) -> Option<u32> {
+    block
+        .split('\\')
+        .rev()
+        .filter_map(|tag| tag.strip_prefix('p'))
+        .find_map(|scale| scale.trim().parse().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEADER: &str = "[Script Info]\nTitle: Test\n\n[V4+ Styles]\nFormat: Name, Fontname\nStyle: Default,Arial\n\n[Events]\n";
+
+    #[test]
+    fn parses_dialogue_events() {
+        let (subs, report) = parse(&format!(
+            "{}Format: Layer, Start, End, Style, Name, MarginL, MarginR, MarginV, Effect, Text\n\
+            Comment: 0,0:00:00.00,0:00:01.00,Default,,0,0,0,,not dialogue\n\
+            Dialogue: 0,0:00:05.00,0:00:06.50,*Default,,0,0,0,,Later\n\
+            Dialogue: 0,0:00:01.00,0:00:02.50,Italics,Sir John,0,0,0,,{{\\i1}}Hello,\\Nthere{{\\i0}}\n",
+            HEADER
+        ));
+        assert!(report.warnings.is_empty() && report.errors.is_empty());
+        assert_eq!(subs.len(), 2);
+        assert_eq!((subs[0].start, subs[0].end, subs[0].line), (1000, 2500, 12));
+        assert_eq!(subs[0].speaker.as_deref(), Some("Sir John"));
+        assert_eq!(subs[0].style.as_deref(), Some("Italics"));
+        assert_eq!(subs[0].text, "{\\i1}Hello,\nthere{\\i0}");
+        assert_eq!(subs[1].style.as_deref(), Some("Default"));
+        assert_eq!(subs[1].speaker, None);
+    }
+
+    #[test]
+    fn drops_drawings_and_reads_custom_formats() {
+        let (subs, report) = parse(&format!(
+            "{}Format: Start, End, Text\n\
+            Dialogue: 0:00:01.00,0:00:02.00,{{\\p1}}m 0 0 l 100 0{{\\p0}}Hi\\hthere\n\
+            Dialogue: broken\n\
+            Dialogue: 0:00:04.00,0:00:03.00,Backwards\n",
+            HEADER
+        ));
+        assert_eq!(subs.len(), 2);
+        assert_eq!(subs[0].text, "Hi there");
+        assert_eq!((subs[1].start, subs[1].end), (4000, 4000));
+        assert_eq!(report.errors.len(), 1);
+        assert_eq!(report.errors[0].line, 11);
+        assert_eq!(report.warnings.len(), 1);
+        assert_eq!(report.warnings[0].line, 12);
+    }
+
+    #[test]
+    fn reports_files_without_events() {
+        let (subs, report) = parse(HEADER);
+        assert!(subs.is_empty());
+        assert_eq!(report.errors[0].message, "no dialogue events found");
+    }
+}
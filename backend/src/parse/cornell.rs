@@ -0,0 +1,516 @@
+use std::collections::{HashMap, HashSet};
+
+use super::ParseReport;
+
+/// Separates the fields on every line of the corpus files.
+pub const SEPARATOR: &str = " +++$+++ ";
+
+/// The decoded content of the four files of the Cornell Movie-Dialogs corpus.
+pub struct CornellFiles<'a> {
+    /// `movie_titles_metadata.txt`
+    pub titles: &'a str,
+    /// `movie_characters_metadata.txt`
+    pub characters: &'a str,
+    /// `movie_lines.txt`
+    pub lines: &'a str,
+    /// `movie_conversations.txt`
+    pub conversations: &'a str,
+}
+
+/// The written files of the corpus, named like the originals.
+#[derive(Debug, Default)]
+pub struct CornellOutput {
+    pub titles: String,
+    pub characters: String,
+    pub lines: String,
+    pub conversations: String,
+}
+
+/// A movie of the corpus with everything the other files have for it.
+#[derive(Debug)]
+pub struct CornellMovie {
+    /// The corpus id like `m0`.
+    pub id: String,
+    pub title: String,
+    pub characters: Vec<CornellCharacter>,
+    /// The lines of the movie in the order of their line ids.
+    pub lines: Vec<CornellLine>,
+    pub conversations: Vec<CornellConversation>,
+}
+
+#[derive(Debug)]
+pub struct CornellCharacter {
+    /// The corpus id like `u0`.
+    pub id: String,
+    pub name: String,
+}
+
+#[derive(Debug)]
+pub struct CornellLine {
+    /// The corpus id like `L1045`.
+    pub id: String,
+    /// The corpus id of the speaking character, missing if the characters file doesn't
+    /// know it.
+    pub character_id: Option<String>,
+    pub text: String,
+}
+
+/// An exchange between two characters of a movie.
+#[derive(Debug)]
+pub struct CornellConversation {
+    pub first_character_id: String,
+    pub second_character_id: String,
+    /// The ids of the lines in the order the corpus lists them.
+    pub line_ids: Vec<String>,
+}
+
+/// Parses the four decoded files of the corpus into its movies.
+///
+/// Every line of the files is split at ` +++$+++ `. Lines of a movie are ordered by the
+/// number in their id, which is how the corpus orders them within a movie even though
+/// `movie_lines.txt` lists them backwards. Issues are reported with the line of the file
+/// they are in and the name of the file in front of the message.
+pub fn parse(files: &CornellFiles) -> (Vec<CornellMovie>, ParseReport) {
+    let mut report = ParseReport::default();
+
+    let mut movies: Vec<CornellMovie> = Vec::new();
+    let mut movie_index: HashMap<String, usize> = HashMap::new();
+    for (line, fields) in records(files.titles, 6) {
+        if fields.len() < 2 {
+            report.error(
+                line,
+                "movie_titles: line has no movie id and title, skipped",
+            );
+            continue;
+        }
+        let (id, title) = (fields[0], fields[1]);
+        if movie_index.contains_key(id) {
+            report.warning(
+                line,
+                format!("movie_titles: movie {} listed twice, skipped", id),
+            );
+            continue;
+        }
+        movie_index.insert(id.to_string(), movies.len());
+        movies.push(CornellMovie {
+            id: id.to_string(),
+            title: title.to_string(),
+            characters: Vec::new(),
+            lines: Vec::new(),
+            conversations: Vec::new(),
+        });
+    }
+
+    let mut character_movies: HashMap<String, usize> = HashMap::new();
+    for (line, fields) in records(files.characters, 6) {
+        if fields.len() < 3 {
+            report.error(line, "movie_characters: line has too few fields, skipped");
+            continue;
+        }
+        let (id, name, movie_id) = (fields[0], fields[1], fields[2]);
+        let movie = match movie_index.get(movie_id) {
+            Some(movie) => *movie,
+            None => {
+                report.error(
+                    line,
+                    format!(
+                        "movie_characters: unknown movie {}, character {} skipped",
+                        movie_id, id
+                    ),
+                );
+                continue;
+            }
+        };
+        if character_movies.contains_key(id) {
+            report.warning(
+                line,
+                format!("movie_characters: character {} listed twice, skipped", id),
+            );
+            continue;
+        }
+        character_movies.insert(id.to_string(), movie);
+        movies[movie].characters.push(CornellCharacter {
+            id: id.to_string(),
+            name: name.to_string(),
+        });
+    }
+
+    let mut line_movies: HashMap<String, usize> = HashMap::new();
+    for (line, fields) in records(files.lines, 5) {
+        if fields.len() < 5 {
+            report.error(line, "movie_lines: line has too few fields, skipped");
+            continue;
+        }
+        let (id, character_id, movie_id, text) = (fields[0], fields[1], fields[2], fields[4]);
+        let movie = match movie_index.get(movie_id) {
+            Some(movie) => *movie,
+            None => {
+                report.error(
+                    line,
+                    format!(
+                        "movie_lines: unknown movie {}, line {} skipped",
+                        movie_id, id
+                    ),
+                );
+                continue;
+            }
+        };
+        if line_movies.contains_key(id) {
+            report.warning(
+                line,
+                format!("movie_lines: line {} listed twice, skipped", id),
+            );
+            continue;
+        }
+        let character_id = if character_movies.get(character_id) == Some(&movie) {
+            Some(character_id.to_string())
+        } else {
+            report.warning(
+                line,
+                format!(
+                    "movie_lines: unknown character {} in movie {}, line {} has no speaker",
+                    character_id, movie_id, id
+                ),
+            );
+            None
+        };
+        line_movies.insert(id.to_string(), movie);
+        movies[movie].lines.push(CornellLine {
+            id: id.to_string(),
+            character_id,
+            text: text.to_string(),
+        });
+    }
+    for movie in &mut movies {
+        movie
+            .lines
+            .sort_by(|a, b| line_number(&a.id).cmp(&line_number(&b.id)));
+    }
+
+    let mut conversation_lines: HashSet<String> = HashSet::new();
+    for (line, fields) in records(files.conversations, 4) {
+        if fields.len() < 4 {
+            report.error(
+                line,
+                "movie_conversations: line has too few fields, skipped",
+            );
+            continue;
+        }
+        let movie_id = fields[2];
+        let movie = match movie_index.get(movie_id) {
+            Some(movie) => *movie,
+            None => {
+                report.error(
+                    line,
+                    format!(
+                        "movie_conversations: unknown movie {}, conversation skipped",
+                        movie_id
+                    ),
+                );
+                continue;
+            }
+        };
+        let mut line_ids = Vec::new();
+        for line_id in parse_list(fields[3]) {
+            if line_movies.get(line_id) != Some(&movie) {
+                report.warning(
+                    line,
+                    format!(
+                        "movie_conversations: unknown line {} in movie {}, left out",
+                        line_id, movie_id
+                    ),
+                );
+            } else if !conversation_lines.insert(line_id.to_string()) {
+                report.warning(
+                    line,
+                    format!(
+                        "movie_conversations: line {} is in an earlier conversation, left out",
+                        line_id
+                    ),
+                );
+            } else {
+                line_ids.push(line_id.to_string());
+            }
+        }
+        if line_ids.is_empty() {
+            report.error(
+                line,
+                "movie_conversations: conversation has no lines, skipped",
+            );
+            continue;
+        }
+        movies[movie].conversations.push(CornellConversation {
+            first_character_id: fields[0].to_string(),
+            second_character_id: fields[1].to_string(),
+            line_ids,
+        });
+    }
+
+    (movies, report)
+}
+
+/// Writes movies into the four files of the corpus.
+///
+/// Character names are written in capitals like the corpus has them. Release year,
+/// rating, votes, genres, gender and credit position aren't known and are left empty or
+/// written as `?`.
+pub fn write(movies: &[CornellMovie]) -> CornellOutput {
+    let mut output = CornellOutput::default();
+    for movie in movies {
+        let title = single_line(&movie.title);
+        push_record(&mut output.titles, &[&movie.id, &title, "", "", "", "[]"]);
+
+        let mut names: HashMap<&str, String> = HashMap::new();
+        for character in &movie.characters {
+            let name = single_line(&character.name).to_uppercase();
+            push_record(
+                &mut output.characters,
+                &[&character.id, &name, &movie.id, &title, "?", "?"],
+            );
+            names.insert(&character.id, name);
+        }
+
+        for line in &movie.lines {
+            let character_id = line.character_id.as_deref().unwrap_or_default();
+            let name = names
+                .get(character_id)
+                .map(String::as_str)
+                .unwrap_or_default();
+            push_record(
+                &mut output.lines,
+                &[
+                    &line.id,
+                    character_id,
+                    &movie.id,
+                    name,
+                    &single_line(&line.text),
+                ],
+            );
+        }
+
+        for conversation in &movie.conversations {
+            let line_ids = conversation
+                .line_ids
+                .iter()
+                .map(|id| format!("'{}'", id))
+                .collect::<Vec<_>>()
+                .join(", ");
+            push_record(
+                &mut output.conversations,
+                &[
+                    &conversation.first_character_id,
+                    &conversation.second_character_id,
+                    &movie.id,
+                    &format!("[{}]", line_ids),
+                ],
+            );
+        }
+    }
+    output
+}
+
+// the non-empty lines of a file with their line number, split into at most `fields` fields
+fn records(text: &str, fields: usize) -> impl Iterator<Item = (usize, Vec<&str>)> {
+    text.trim_start_matches('\u{feff}')
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        // the separator loses its trailing space when a line ends with an empty field
+        .map(move |(i, line)| {
+            (
+                i + 1,
+                line.splitn(fields, "+++$+++").map(str::trim).collect(),
+            )
+        })
+}
+
+// the items of a python list like ['L194', 'L195']
+fn parse_list(list: &str) -> impl Iterator<Item = &str> {
+    list.trim()
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .split(',')
+        .map(|item| item.trim().trim_matches(|c| c == '\'' || c == '"'))
+        .filter(|item| !item.is_empty())
+}
+
+// the number of an id like L1045, ids without one go last
+fn line_number(id: &str) -> (u64, &str) {
+    let number = id.trim_start_matches(|c: char| !c.is_ascii_digit());
+    (number.parse().unwrap_or(u64::MAX), id)
+}
+
+// every record is a single line, so line breaks become spaces
+fn single_line(text: &str) -> String {
+    text.lines().map(str::trim).collect::<Vec<_>>().join(" ")
+}
+
+fn push_record(output: &mut String, fields: &[&str]) {
+    output.push_str(&fields.join(SEPARATOR));
+    output.push('\n');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TITLES: &str = "m0 +++$+++ 10 things i hate about you +++$+++ 1999 +++$+++ 6.90 +++$+++ 62847 +++$+++ ['comedy', 'romance']\n\
+        m0 +++$+++ duplicate +++$+++ 1999 +++$+++ 1 +++$+++ 1 +++$+++ []\n";
+    const CHARACTERS: &str =
+        "u0 +++$+++ BIANCA +++$+++ m0 +++$+++ 10 things i hate about you +++$+++ f +++$+++ 4\n\
+        u2 +++$+++ CAMERON +++$+++ m0 +++$+++ 10 things i hate about you +++$+++ m +++$+++ 3\n\
+        u9 +++$+++ NOBODY +++$+++ m7 +++$+++ unknown +++$+++ ? +++$+++ ?\n";
+    const LINES: &str = "L197 +++$+++ u2 +++$+++ m0 +++$+++ CAMERON +++$+++ Not the hacking and gagging part.\n\
+        L196 +++$+++ u0 +++$+++ m0 +++$+++ BIANCA +++$+++ Let's go.\n\
+        L195 +++$+++ u2 +++$+++ m0 +++$+++ CAMERON +++$+++ Well, I thought we'd start with pronunciation.\n\
+        L194 +++$+++ u0 +++$+++ m0 +++$+++ BIANCA +++$+++ Can we make this quick?\n\
+        L1000 +++$+++ u5 +++$+++ m0 +++$+++ SOMEONE +++$+++ Who am I?\n\
+        L474 +++$+++ u0 +++$+++ m0 +++$+++ BIANCA +++$+++\n";
+    const CONVERSATIONS: &str =
+        "u0 +++$+++ u2 +++$+++ m0 +++$+++ ['L194', 'L195', 'L196', 'L197', 'L999']\n\
+        u0 +++$+++ u2 +++$+++ m0 +++$+++ ['L197']\n";
+
+    fn files() -> CornellFiles<'static> {
+        CornellFiles {
+            titles: TITLES,
+            characters: CHARACTERS,
+            lines: LINES,
+            conversations: CONVERSATIONS,
+        }
+    }
+
+    #[test]
+    fn parses_the_corpus() {
+        let (movies, report) = parse(&files());
+        assert_eq!(movies.len(), 1);
+        let movie = &movies[0];
+        assert_eq!(
+            (movie.id.as_str(), movie.title.as_str()),
+            ("m0", "10 things i hate about you")
+        );
+        assert_eq!(movie.characters.len(), 2);
+
+        let lines: Vec<(&str, Option<&str>, &str)> = movie
+            .lines
+            .iter()
+            .map(|line| {
+                (
+                    line.id.as_str(),
+                    line.character_id.as_deref(),
+                    line.text.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            lines,
+            vec![
+                ("L194", Some("u0"), "Can we make this quick?"),
+                (
+                    "L195",
+                    Some("u2"),
+                    "Well, I thought we'd start with pronunciation."
+                ),
+                ("L196", Some("u0"), "Let's go."),
+                ("L197", Some("u2"), "Not the hacking and gagging part."),
+                ("L474", Some("u0"), ""),
+                ("L1000", None, "Who am I?"),
+            ]
+        );
+
+        assert_eq!(movie.conversations.len(), 1);
+        assert_eq!(
+            movie.conversations[0].line_ids,
+            ["L194", "L195", "L196", "L197"]
+        );
+
+        let warnings: Vec<(usize, &str)> = report
+            .warnings
+            .iter()
+            .map(|issue| (issue.line, issue.message.as_str()))
+            .collect();
+        assert_eq!(
+            warnings,
+            vec![
+                (2, "movie_titles: movie m0 listed twice, skipped"),
+                (
+                    5,
+                    "movie_lines: unknown character u5 in movie m0, line L1000 has no speaker"
+                ),
+                (
+                    1,
+                    "movie_conversations: unknown line L999 in movie m0, left out"
+                ),
+                (
+                    2,
+                    "movie_conversations: line L197 is in an earlier conversation, left out"
+                ),
+            ]
+        );
+        let errors: Vec<(usize, &str)> = report
+            .errors
+            .iter()
+            .map(|issue| (issue.line, issue.message.as_str()))
+            .collect();
+        assert_eq!(
+            errors,
+            vec![
+                (
+                    3,
+                    "movie_characters: unknown movie m7, character u9 skipped"
+                ),
+                (2, "movie_conversations: conversation has no lines, skipped"),
+            ]
+        );
+    }
+
+    #[test]
+    fn writes_what_it_parses() {
+        let (movies, _) = parse(&files());
+        let output = write(&movies);
+        assert_eq!(
+            output.titles,
+            "m0 +++$+++ 10 things i hate about you +++$+++  +++$+++  +++$+++  +++$+++ []\n"
+        );
+        assert!(output.lines.starts_with(
+            "L194 +++$+++ u0 +++$+++ m0 +++$+++ BIANCA +++$+++ Can we make this quick?\n"
+        ));
+        assert!(output
+            .lines
+            .contains("L1000 +++$+++  +++$+++ m0 +++$+++  +++$+++ Who am I?\n"));
+        assert_eq!(
+            output.conversations,
+            "u0 +++$+++ u2 +++$+++ m0 +++$+++ ['L194', 'L195', 'L196', 'L197']\n"
+        );
+
+        let (reparsed, report) = parse(&CornellFiles {
+            titles: &output.titles,
+            characters: &output.characters,
+            lines: &output.lines,
+            conversations: &output.conversations,
+        });
+        assert_eq!(reparsed[0].lines.len(), movies[0].lines.len());
+        assert_eq!(
+            reparsed[0].conversations[0].line_ids,
+            movies[0].conversations[0].line_ids
+        );
+        // only the line without a known speaker is reported again
+        assert_eq!(report.warnings.len(), 1);
+        assert!(report.errors.is_empty());
+    }
+
+    #[test]
+    fn keeps_records_on_one_line() {
+        let movies = vec![CornellMovie {
+            id: "m1".to_string(),
+            title: "Title".to_string(),
+            characters: Vec::new(),
+            lines: vec![CornellLine {
+                id: "L1".to_string(),
+                character_id: None,
+                text: "two\nlines  here".to_string(),
+            }],
+            conversations: Vec::new(),
+        }];
+        assert!(write(&movies).lines.ends_with("+++$+++ two lines  here\n"));
+    }
+}
@@ -0,0 +1,165 @@
+use async_graphql::Enum;
+
+use super::{markup, Sub};
+
+/// How the sentences of a split cue are timed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+pub enum DialogueTiming {
+    /// Every sentence gets the full time range of the cue.
+    Share,
+    /// The time range of the cue is divided by the length of each sentence.
+    Proportional,
+}
+
+/// Splits cues holding several speakers, like "- Where were you?\n- At the castle.",
+/// into one cue per dash.
+///
+/// A cue is only split if that gives at least two parts, lines without a dash belong to
+/// the part above them. The first part doesn't need a dash, as some files only mark the
+/// answer.
+pub fn split(subs: Vec<Sub>, timing: DialogueTiming) -> Vec<Sub> {
+    let mut split = Vec::with_capacity(subs.len());
+    for sub in subs {
+        let parts = parts(&sub.text);
+        if parts.len() < 2 {
+            split.push(sub);
+            continue;
+        }
+
+        let duration = sub.end - sub.start;
+        let total = parts
+            .iter()
+            .map(|part| part.chars().count())
+            .sum::<usize>()
+            .max(1) as i64;
+        let mut start = sub.start;
+        let mut length = 0;
+        for (i, part) in parts.iter().enumerate() {
+            length += part.chars().count() as i64;
+            let (part_start, part_end) = match timing {
+                DialogueTiming::Share => (sub.start, sub.end),
+                DialogueTiming::Proportional if i == parts.len() - 1 => (start, sub.end),
+                DialogueTiming::Proportional => (start, sub.start + duration * length / total),
+            };
+            start = part_end;
+
+            split.push(Sub {
+                start: part_start,
+                end: part_end,
+                text: part.clone(),
+                ..sub.clone()
+            });
+        }
+    }
+    split
+}
+
+fn parts(text: &str) -> Vec<String> {
+    let mut parts: Vec<String> = Vec::new();
+    for line in text.lines().map(str::trim).filter(|line| !line.is_empty()) {
+        match strip_dash(line) {
+            Some(line) => parts.push(line),
+            None => match parts.last_mut() {
+                Some(part) => {
+                    part.push('\n');
+                    part.push_str(line);
+                }
+                None => parts.push(line.to_string()),
+            },
+        }
+    }
+    parts
+}
+
+// "- Hello" and "–Hello" are dialogue dashes, "--and then" is an interruption. Formatting
+// tags in front of the dash, like in "<i>- Hello", are kept.
+fn strip_dash(line: &str) -> Option<String> {
+    let (tags, line) = line.split_at(markup::leading_tags(line));
+    let rest = line.strip_prefix(['-', '–', '—'])?;
+    if rest.starts_with(['-', '–', '—']) {
+        return None;
+    }
+    let rest = rest.trim_start();
+    (!markup::strip(rest).is_empty()).then(|| format!("{}{}", tags, rest))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::SubKind;
+
+    fn sub(start: i64, end: i64, text: &str) -> Sub {
+        Sub {
+            kind: SubKind::Dialogue,
+            index: Some(1),
+            line: 3,
+            start,
+            end,
+            text: text.to_string(),
+            speaker: None,
+            style: None,
+            markup: Vec::new(),
+        }
+    }
+
+    fn parts(subs: &[Sub]) -> Vec<(i64, i64, &str)> {
+        subs.iter()
+            .map(|sub| (sub.start, sub.end, sub.text.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn splits_dashes_proportionally() {
+        let subs = split(
+            vec![sub(0, 3000, "- Where were you?\n- At home.")],
+            DialogueTiming::Proportional,
+        );
+        // 15 and 8 characters
+        assert_eq!(
+            parts(&subs),
+            vec![(0, 1956, "Where were you?"), (1956, 3000, "At home.")]
+        );
+        assert!(subs.iter().all(|sub| sub.line == 3 && sub.index == Some(1)));
+    }
+
+    #[test]
+    fn shares_the_timing() {
+        let subs = split(
+            vec![sub(0, 3000, "Where were you?\n–At home.\n—Alone.")],
+            DialogueTiming::Share,
+        );
+        assert_eq!(
+            parts(&subs),
+            vec![
+                (0, 3000, "Where were you?"),
+                (0, 3000, "At home."),
+                (0, 3000, "Alone.")
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_continuations_and_tags() {
+        let subs = split(
+            vec![sub(0, 1000, "<i>- I was\nat the castle.\n<i>- Why?")],
+            DialogueTiming::Share,
+        );
+        assert_eq!(
+            parts(&subs),
+            vec![(0, 1000, "<i>I was\nat the castle."), (0, 1000, "<i>Why?")]
+        );
+    }
+
+    #[test]
+    fn leaves_single_speakers_and_interruptions_alone() {
+        let texts = [
+            "- Just me.",
+            "I was going to--\n--say something",
+            "Plain text",
+        ];
+        for text in texts {
+            let subs = split(vec![sub(0, 1000, text)], DialogueTiming::Proportional);
+            assert_eq!(parts(&subs), vec![(0, 1000, text)]);
+        }
+    }
+}
@@ -0,0 +1,113 @@
+use chardetng::EncodingDetector;
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8};
+
+// how many bytes are looked at when guessing whether a file is utf-16 without a bom
+const UTF_16_SAMPLE_SIZE: usize = 4096;
+
+/// Decodes subtitle bytes into a string.
+///
+/// If `encoding` is given it is used as is, otherwise the encoding is detected with
+/// [`detect`]. Returns the decoded string together with the encoding that was used.
+pub fn decode(bytes: &[u8], encoding: Option<&'static Encoding>) -> (String, &'static Encoding) {
+    let encoding = encoding.unwrap_or_else(|| detect(bytes));
+    // decode_with_bom_removal strips a bom matching the encoding but never switches encodings
+    let (decoded, _) = encoding.decode_with_bom_removal(bytes);
+    (decoded.into_owned(), encoding)
+}
+
+/// Encodes text for a file, characters the encoding doesn't have become `?`.
+pub fn encode(text: &str, encoding: &'static Encoding) -> Vec<u8> {
+    let (bytes, _, unmappable) = encoding.encode(text);
+    if !unmappable {
+        return bytes.into_owned();
+    }
+    // encoding_rs would write html entities instead
+    let mut bytes = Vec::with_capacity(text.len());
+    let mut buffer = [0; 4];
+    for c in text.chars() {
+        match encoding.encode(c.encode_utf8(&mut buffer)) {
+            (encoded, _, false) => bytes.extend_from_slice(&encoded),
+            (_, _, true) => bytes.push(b'?'),
+        }
+    }
+    bytes
+}
+
+/// Guesses the encoding of subtitle bytes.
+///
+/// A byte order mark always wins. Without one, utf-16 is recognised by its zero bytes,
+/// valid utf-8 is taken as utf-8 and everything else is handed to chardetng.
+pub fn detect(bytes: &[u8]) -> &'static Encoding {
+    if let Some((encoding, _)) = Encoding::for_bom(bytes) {
+        return encoding;
+    }
+
+    if let Some(encoding) = detect_utf_16(bytes) {
+        return encoding;
+    }
+
+    if std::str::from_utf8(bytes).is_ok() {
+        return UTF_8;
+    }
+
+    let mut detector = EncodingDetector::new();
+    detector.feed(bytes, true);
+    detector.guess(None, true)
+}
+
+// subtitles are mostly latin text, so utf-16 without a bom has a zero byte in every
+// other position: odd positions for little endian, even positions for big endian
+fn detect_utf_16(bytes: &[u8]) -> Option<&'static Encoding> {
+    let sample = &bytes[..bytes.len().min(UTF_16_SAMPLE_SIZE)];
+    let pairs = sample.len() / 2;
+    if pairs == 0 {
+        return None;
+    }
+
+    let even_zeros = sample.iter().step_by(2).filter(|b| **b == 0).count();
+    let odd_zeros = sample
+        .iter()
+        .skip(1)
+        .step_by(2)
+        .filter(|b| **b == 0)
+        .count();
+
+    if odd_zeros * 2 > pairs && even_zeros * 10 < pairs {
+        Some(UTF_16LE)
+    } else if even_zeros * 2 > pairs && odd_zeros * 10 < pairs {
+        Some(UTF_16BE)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use encoding_rs::WINDOWS_1252;
+
+    #[test]
+    fn detects_encodings() {
+        assert_eq!(detect("Café".as_bytes()), UTF_8);
+        assert_eq!(detect(b"\xef\xbb\xbfHi"), UTF_8);
+        assert_eq!(detect(b"\xff\xfeH\0i\0"), UTF_16LE);
+        assert_eq!(detect(b"\0H\0i\0!\0\n"), UTF_16BE);
+        let latin = b"Il \xe9tait une fois, dans un caf\xe9 pr\xe8s de la for\xeat.";
+        assert_eq!(
+            decode(latin, None).0,
+            "Il était une fois, dans un café près de la forêt."
+        );
+    }
+
+    #[test]
+    fn removes_the_bom() {
+        let (text, encoding) = decode(b"\xef\xbb\xbfHi", None);
+        assert_eq!((text.as_str(), encoding), ("Hi", UTF_8));
+    }
+
+    #[test]
+    fn encodes_unmappable_characters_as_question_marks() {
+        assert_eq!(encode("Café ♥", WINDOWS_1252), b"Caf\xe9 ?");
+        assert_eq!(encode("Café", UTF_8), "Café".as_bytes());
+    }
+}
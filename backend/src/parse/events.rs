@@ -0,0 +1,147 @@
+use super::{Sub, SubKind};
+
+/// Takes bracketed annotations like "[door slams]" or "(laughs)" out of the dialogue.
+///
+/// Every annotation becomes a cue of its own with the timing of the cue it was found in,
+/// whatever is left of the text stays dialogue.
+pub fn extract(subs: Vec<Sub>) -> Vec<Sub> {
+    let mut extracted = Vec::with_capacity(subs.len());
+    for sub in subs {
+        let (events, text) = split_events(&sub.text);
+        for event in events {
+            extracted.push(Sub {
+                kind: SubKind::SoundEvent,
+                text: event,
+                speaker: None,
+                ..sub.clone()
+            });
+        }
+        if !text.is_empty() {
+            extracted.push(Sub { text, ..sub });
+        }
+    }
+    extracted
+}
+
+// returns the annotations and the text without them
+fn split_events(text: &str) -> (Vec<String>, String) {
+    let mut events = Vec::new();
+    let mut rest = String::with_capacity(text.len());
+    let mut remaining = text;
+
+    while let Some(open) = remaining.find(['[', '(']) {
+        let close = match &remaining[open..open + 1] {
+            "[" => ']',
+            _ => ')',
+        };
+        let end = match remaining[open..].find(close) {
+            Some(end) => open + end,
+            None => break,
+        };
+
+        let event = remaining[open + 1..end].trim();
+        if !event.is_empty() {
+            events.push(event.to_string());
+        }
+        rest.push_str(&remaining[..open]);
+        rest.push(' ');
+        remaining = &remaining[end + 1..];
+    }
+    rest.push_str(remaining);
+
+    // a dash or label colon that only introduced the annotation is no dialogue either
+    let text = rest
+        .lines()
+        .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|line| !is_introduction(line))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    (events, text)
+}
+
+// a line like "-" or "JOHN:" with nothing left to say
+fn is_introduction(line: &str) -> bool {
+    let label = match line.strip_suffix(':') {
+        Some(label) => label,
+        None => line,
+    };
+    !label.chars().any(char::is_alphanumeric)
+        || line.ends_with(':') && !label.chars().any(char::is_lowercase)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sub(text: &str) -> Sub {
+        Sub {
+            kind: SubKind::Dialogue,
+            index: Some(4),
+            line: 10,
+            start: 1000,
+            end: 2000,
+            text: text.to_string(),
+            speaker: Some("John".to_string()),
+            style: None,
+            markup: Vec::new(),
+        }
+    }
+
+    fn extracted(text: &str) -> Vec<(SubKind, String, Option<String>)> {
+        extract(vec![sub(text)])
+            .into_iter()
+            .map(|sub| {
+                assert_eq!((sub.start, sub.end, sub.line), (1000, 2000, 10));
+                (sub.kind, sub.text, sub.speaker)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn moves_annotations_into_sound_events() {
+        assert_eq!(
+            extracted("[door slams] Who's there? (whispers)"),
+            vec![
+                (SubKind::SoundEvent, "door slams".to_string(), None),
+                (SubKind::SoundEvent, "whispers".to_string(), None),
+                (
+                    SubKind::Dialogue,
+                    "Who's there?".to_string(),
+                    Some("John".to_string())
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn drops_dialogue_left_without_words() {
+        assert_eq!(
+            extracted("- [gunshot]\nJOHN: [laughs]"),
+            vec![
+                (SubKind::SoundEvent, "gunshot".to_string(), None),
+                (SubKind::SoundEvent, "laughs".to_string(), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_unclosed_and_empty_brackets() {
+        assert_eq!(
+            extracted("I said [ and ( then"),
+            vec![(
+                SubKind::Dialogue,
+                "I said [ and ( then".to_string(),
+                Some("John".to_string())
+            )]
+        );
+        assert_eq!(
+            extracted("Hello [] there"),
+            vec![(
+                SubKind::Dialogue,
+                "Hello there".to_string(),
+                Some("John".to_string())
+            )]
+        );
+    }
+}
@@ -0,0 +1,195 @@
+use roxmltree::{Document, Node};
+
+use super::{
+    markup,
+    script::{self, Screenplay, ScriptLine, ScriptScene},
+    ParseReport,
+};
+
+/// Parses a decoded Final Draft (.fdx) screenplay.
+///
+/// `Scene Heading` paragraphs start new scenes, `Character` paragraphs set the speaker of
+/// the `Dialogue` paragraphs that follow and a `Parenthetical` is kept on the next line of
+/// dialogue. Bold, italic and underlined text becomes markup.
+pub fn parse(text: &str) -> (Screenplay, ParseReport) {
+    let mut report = ParseReport::default();
+    let mut screenplay = Screenplay::default();
+
+    let document = match Document::parse(text.trim_start_matches('\u{feff}')) {
+        Ok(document) => document,
+        Err(error) => {
+            report.error(error.pos().row as usize, format!("invalid xml: {}", error));
+            return (screenplay, report);
+        }
+    };
+
+    let content = document
+        .descendants()
+        .find(|node| node.has_tag_name("Content"));
+    let content = match content {
+        Some(content) => content,
+        None => {
+            report.error(1, "file has no Content element");
+            return (screenplay, report);
+        }
+    };
+
+    let mut scene = ScriptScene::default();
+    let mut speaker: Option<String> = None;
+    let mut parenthetical: Option<String> = None;
+
+    // descendants and not children, dual dialogue nests its paragraphs
+    for paragraph in content
+        .descendants()
+        .filter(|node| node.has_tag_name("Paragraph"))
+    {
+        let line = document.text_pos_at(paragraph.range().start).row as usize;
+        let tagged = tagged_text(paragraph);
+        let plain = markup::strip(&tagged);
+
+        match paragraph.attribute("Type").unwrap_or_default() {
+            "Scene Heading" => {
+                let finished = std::mem::replace(
+                    &mut scene,
+                    ScriptScene {
+                        location: script::heading_location(&plain),
+                        heading: Some(plain).filter(|heading| !heading.is_empty()),
+                        lines: Vec::new(),
+                    },
+                );
+                if finished.heading.is_some() || !finished.lines.is_empty() {
+                    screenplay.scenes.push(finished);
+                }
+                speaker = None;
+            }
+            "Character" => {
+                if parenthetical.take().is_some() {
+                    report.warning(line, "parenthetical without dialogue");
+                }
+                let name = script::cue_name(&plain);
+                speaker = (!name.is_empty()).then(|| script::character_name(&name));
+            }
+            "Parenthetical" => {
+                let inner = plain.trim_start_matches('(').trim_end_matches(')').trim();
+                parenthetical = Some(inner.to_string());
+            }
+            "Dialogue" => match &speaker {
+                Some(speaker) if !plain.is_empty() => {
+                    let (text, markup) = markup::extract(&tagged);
+                    scene.lines.push(ScriptLine {
+                        line,
+                        speaker: speaker.clone(),
+                        parenthetical: parenthetical.take(),
+                        text,
+                        markup,
+                    });
+                }
+                Some(_) => {}
+                None => report.warning(line, "dialogue without character, skipped"),
+            },
+            // action and transitions end a speech
+            _ => speaker = None,
+        }
+    }
+    if scene.heading.is_some() || !scene.lines.is_empty() {
+        screenplay.scenes.push(scene);
+    }
+
+    if screenplay.scenes.iter().all(|scene| scene.lines.is_empty()) {
+        report.error(1, "no dialogue found");
+    }
+
+    (screenplay, report)
+}
+
+// the text of a paragraph with its styled runs wrapped in tags markup::extract understands
+fn tagged_text(paragraph: Node) -> String {
+    let mut tagged = String::new();
+    for text in paragraph
+        .children()
+        .filter(|node| node.has_tag_name("Text"))
+    {
+        let content = text.text().unwrap_or_default();
+        let styles = text.attribute("Style").unwrap_or_default();
+        let tags: Vec<&str> = [("Bold", "b"), ("Italic", "i"), ("Underline", "u")]
+            .into_iter()
+            .filter(|(style, _)| styles.split('+').any(|part| part == *style))
+            .map(|(_, tag)| tag)
+            .collect();
+
+        for tag in &tags {
+            tagged.push_str(&format!("<{}>", tag));
+        }
+        tagged.push_str(content);
+        for tag in tags.iter().rev() {
+            tagged.push_str(&format!("</{}>", tag));
+        }
+    }
+    tagged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCREENPLAY: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<FinalDraft DocumentType="Script" Version="3">
+<Content>
+<Paragraph Type="Dialogue"><Text>Orphan</Text></Paragraph>
+<Paragraph Type="Scene Heading"><Text>EXT. BEACH - DAY</Text></Paragraph>
+<Paragraph Type="Character"><Text>KAT (CONT'D)</Text></Paragraph>
+<Paragraph Type="Parenthetical"><Text>(smiling)</Text></Paragraph>
+<Paragraph Type="Dialogue"><Text>It's </Text><Text Style="Bold+Italic">great</Text><Text>.</Text></Paragraph>
+<Paragraph Type="Dialogue"><Text>Really.</Text></Paragraph>
+<Paragraph Type="Action"><Text>She leaves.</Text></Paragraph>
+<Paragraph Type="Dialogue"><Text>Nobody</Text></Paragraph>
+</Content>
+</FinalDraft>"#;
+
+    #[test]
+    fn parses_scenes_and_dialogue() {
+        let (screenplay, report) = parse(SCREENPLAY);
+        assert_eq!(screenplay.scenes.len(), 1);
+        let scene = &screenplay.scenes[0];
+        assert_eq!(scene.heading.as_deref(), Some("EXT. BEACH - DAY"));
+        assert_eq!(scene.location.as_deref(), Some("BEACH"));
+
+        let lines: Vec<(usize, &str, Option<&str>, &str)> = scene
+            .lines
+            .iter()
+            .map(|line| {
+                (
+                    line.line,
+                    line.speaker.as_str(),
+                    line.parenthetical.as_deref(),
+                    line.text.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            lines,
+            vec![
+                (8, "Kat", Some("smiling"), "It's great."),
+                (9, "Kat", None, "Really."),
+            ]
+        );
+        let markup = &scene.lines[0].markup;
+        assert_eq!(markup.len(), 2);
+        assert!(markup
+            .iter()
+            .all(|span| (span.start_char, span.end_char) == (5, 10)));
+
+        let warnings: Vec<usize> = report.warnings.iter().map(|issue| issue.line).collect();
+        assert_eq!(warnings, vec![4, 11]);
+        assert!(report.errors.is_empty());
+    }
+
+    #[test]
+    fn reports_invalid_files() {
+        let (_, report) = parse("<FinalDraft>");
+        assert!(report.errors[0].message.starts_with("invalid xml"));
+
+        let (_, report) = parse("<FinalDraft/>");
+        assert_eq!(report.errors[0].message, "file has no Content element");
+    }
+}
@@ -0,0 +1,342 @@
+use super::{
+    markup,
+    script::{self, Screenplay, ScriptLine, ScriptScene},
+    ParseReport,
+};
+
+/// Parses a decoded Fountain screenplay.
+///
+/// Scene headings start new scenes and character cues with their dialogue become lines.
+/// Action, transitions, sections and everything else without dialogue is skipped, as
+/// are the title page, notes and the boneyard. Emphasis like `*italic*` becomes markup.
+pub fn parse(text: &str) -> (Screenplay, ParseReport) {
+    let mut report = ParseReport::default();
+
+    let text = text.trim_start_matches('\u{feff}');
+    let text = text.replace("\r\n", "\n").replace('\r', "\n");
+    let text = remove_between(&text, "/*", "*/");
+    let text = remove_between(&text, "[[", "]]");
+    let lines: Vec<&str> = text.split('\n').map(str::trim_end).collect();
+
+    let mut screenplay = Screenplay::default();
+    let mut scene = ScriptScene::default();
+
+    let mut i = title_page_end(&lines);
+    let mut after_blank = true;
+    while i < lines.len() {
+        let line = lines[i].trim();
+        let next_blank = lines.get(i + 1).is_none_or(|next| next.trim().is_empty());
+
+        if line.is_empty() {
+            after_blank = true;
+            i += 1;
+            continue;
+        }
+
+        if after_blank && next_blank {
+            if let Some(heading) = heading(line) {
+                let finished = std::mem::replace(
+                    &mut scene,
+                    ScriptScene {
+                        location: script::heading_location(&heading),
+                        heading: Some(heading),
+                        lines: Vec::new(),
+                    },
+                );
+                if finished.heading.is_some() || !finished.lines.is_empty() {
+                    screenplay.scenes.push(finished);
+                }
+                after_blank = false;
+                i += 1;
+                continue;
+            }
+        }
+
+        if after_blank && !next_blank {
+            if let Some(speaker) = character(line) {
+                let block_end = lines[i + 1..]
+                    .iter()
+                    .position(|line| line.trim().is_empty())
+                    .map_or(lines.len(), |len| i + 1 + len);
+                dialogue(&speaker, &lines, i + 1, block_end, &mut scene, &mut report);
+                after_blank = false;
+                i = block_end;
+                continue;
+            }
+        }
+
+        after_blank = false;
+        i += 1;
+    }
+    if scene.heading.is_some() || !scene.lines.is_empty() {
+        screenplay.scenes.push(scene);
+    }
+
+    if screenplay.scenes.iter().all(|scene| scene.lines.is_empty()) {
+        report.error(1, "no dialogue found");
+    }
+
+    (screenplay, report)
+}
+
+// reads the dialogue below a character cue, every parenthetical starts a new line
+fn dialogue(
+    speaker: &str,
+    lines: &[&str],
+    start: usize,
+    end: usize,
+    scene: &mut ScriptScene,
+    report: &mut ParseReport,
+) {
+    let mut parenthetical: Option<String> = None;
+    let mut text: Vec<&str> = Vec::new();
+    let mut first = start;
+
+    let mut flush = |parenthetical: Option<String>, text: &mut Vec<&str>, first: usize| {
+        if text.is_empty() {
+            return;
+        }
+        let (plain, markup) = markup::extract(&emphasis(&text.join(" ")));
+        text.clear();
+        scene.lines.push(ScriptLine {
+            line: first + 1,
+            speaker: speaker.to_string(),
+            parenthetical,
+            text: plain,
+            markup,
+        });
+    };
+
+    for (i, line) in lines.iter().enumerate().take(end).skip(start) {
+        let line = line.trim();
+        if line.starts_with('(') && line.ends_with(')') {
+            if parenthetical.is_some() && text.is_empty() {
+                report.warning(i + 1, "parenthetical without dialogue");
+            }
+            flush(parenthetical.take(), &mut text, first);
+            parenthetical = Some(line[1..line.len() - 1].trim().to_string());
+            first = i + 1;
+            continue;
+        }
+        text.push(line.trim_start_matches('~'));
+    }
+
+    if text.is_empty() {
+        report.warning(start, "character cue without dialogue");
+    }
+    flush(parenthetical, &mut text, first);
+}
+
+// the heading of a forced ".HEADING" or a line starting with INT., EXT. and the like,
+// without the scene number like "#12#"
+fn heading(line: &str) -> Option<String> {
+    let heading = match line.strip_prefix('.') {
+        Some(forced) if !forced.starts_with('.') => forced,
+        Some(_) => return None,
+        None if script::is_heading(line) => line,
+        None => return None,
+    };
+    let heading = match heading.trim_end().strip_suffix('#') {
+        Some(numbered) => numbered
+            .rfind('#')
+            .map_or(heading, |hash| &numbered[..hash]),
+        None => heading,
+    };
+    Some(heading.trim().to_string())
+}
+
+// the name of a forced "@McCLANE", which is kept as written, or of a line in capitals like
+// "SIR JOHN (V.O.)", which becomes "Sir John"
+fn character(line: &str) -> Option<String> {
+    // ^ marks dual dialogue, which is read as one after the other
+    let line = line.trim_end_matches('^').trim();
+    if let Some(forced) = line.strip_prefix('@') {
+        return Some(script::cue_name(forced)).filter(|name| !name.is_empty());
+    }
+
+    let name = script::cue_name(line);
+    let is_cue = name.chars().any(char::is_alphabetic)
+        && !name.chars().any(char::is_lowercase)
+        && !line.ends_with("TO:")
+        && !line.starts_with(['!', '>', '#', '=', '~']);
+    is_cue.then(|| script::character_name(&name))
+}
+
+// the index of the first line after the title page, which is a block of "Key: value"
+// lines at the very top
+fn title_page_end(lines: &[&str]) -> usize {
+    let first = lines.first().map_or("", |line| line.trim());
+    let is_key = first.split_once(':').is_some_and(|(key, _)| {
+        !key.is_empty() && key.chars().all(|c| c.is_alphabetic() || c == ' ')
+    });
+    if !is_key {
+        return 0;
+    }
+    lines
+        .iter()
+        .position(|line| line.trim().is_empty())
+        .unwrap_or(lines.len())
+}
+
+// removes everything between the markers but keeps the line breaks, so line numbers stay
+fn remove_between(text: &str, open: &str, close: &str) -> String {
+    let mut kept = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find(open) {
+        kept.push_str(&rest[..start]);
+        let end = rest[start..]
+            .find(close)
+            .map_or(rest.len(), |end| start + end + close.len());
+        kept.extend(rest[start..end].chars().filter(|c| *c == '\n'));
+        rest = &rest[end..];
+    }
+    kept.push_str(rest);
+    kept
+}
+
+// turns fountain emphasis into tags markup::extract understands: ***bold italic***,
+// **bold**, *italic* and _underline_, with \* and \_ for literal characters
+fn emphasis(text: &str) -> String {
+    let mut tagged = String::with_capacity(text.len());
+    let (mut italic, mut bold, mut underline) = (false, false, false);
+
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        if let Some(escaped) = rest.strip_prefix('\\').and_then(|rest| rest.chars().next()) {
+            if escaped == '*' || escaped == '_' {
+                tagged.push(escaped);
+                rest = &rest[2..];
+                continue;
+            }
+        }
+        if rest.starts_with("***") {
+            toggle(&mut bold, "b", &mut tagged);
+            toggle(&mut italic, "i", &mut tagged);
+            rest = &rest[3..];
+        } else if rest.starts_with("**") {
+            toggle(&mut bold, "b", &mut tagged);
+            rest = &rest[2..];
+        } else if c == '*' {
+            toggle(&mut italic, "i", &mut tagged);
+            rest = &rest[1..];
+        } else if c == '_' {
+            toggle(&mut underline, "u", &mut tagged);
+            rest = &rest[1..];
+        } else {
+            tagged.push(c);
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+    tagged
+}
+
+fn toggle(open: &mut bool, tag: &str, tagged: &mut String) {
+    tagged.push_str(if *open { "</" } else { "<" });
+    tagged.push_str(tag);
+    tagged.push('>');
+    *open = !*open;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::markup::MarkupKind;
+
+    const SCREENPLAY: &str = "Title: Test
+Author: Me
+
+INT. CASTLE - HALL - NIGHT
+
+John enters.
+
+SIR JOHN (V.O.)
+(whispering)
+Where *were* you?
+(louder)
+**Answer** me.
+
+@McCLANE
+Yippee.
+
+CUT TO:
+
+.FLASHBACK #12#
+
+ANNA ^
+/* a note */ Hi [[ note ]]there.
+";
+
+    #[test]
+    fn parses_scenes_and_dialogue() {
+        let (screenplay, report) = parse(SCREENPLAY);
+        assert!(report.warnings.is_empty() && report.errors.is_empty());
+
+        let scenes: Vec<(Option<&str>, Option<&str>, usize)> = screenplay
+            .scenes
+            .iter()
+            .map(|scene| {
+                (
+                    scene.heading.as_deref(),
+                    scene.location.as_deref(),
+                    scene.lines.len(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            scenes,
+            vec![
+                (Some("INT. CASTLE - HALL - NIGHT"), Some("CASTLE - HALL"), 3),
+                (Some("FLASHBACK"), None, 1),
+            ]
+        );
+
+        let lines: Vec<(usize, &str, Option<&str>, &str)> = screenplay
+            .scenes
+            .iter()
+            .flat_map(|scene| &scene.lines)
+            .map(|line| {
+                (
+                    line.line,
+                    line.speaker.as_str(),
+                    line.parenthetical.as_deref(),
+                    line.text.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            lines,
+            vec![
+                (10, "Sir John", Some("whispering"), "Where were you?"),
+                (12, "Sir John", Some("louder"), "Answer me."),
+                (15, "McCLANE", None, "Yippee."),
+                (22, "Anna", None, "Hi there."),
+            ]
+        );
+    }
+
+    #[test]
+    fn turns_emphasis_into_markup() {
+        let (screenplay, _) = parse(SCREENPLAY);
+        let lines = &screenplay.scenes[0].lines;
+        assert_eq!(lines[0].markup.len(), 1);
+        assert_eq!(lines[0].markup[0].kind, MarkupKind::Italic);
+        assert_eq!(
+            (lines[0].markup[0].start_char, lines[0].markup[0].end_char),
+            (6, 10)
+        );
+        assert_eq!(lines[1].markup[0].kind, MarkupKind::Bold);
+        assert_eq!(
+            (lines[1].markup[0].start_char, lines[1].markup[0].end_char),
+            (0, 6)
+        );
+        assert_eq!(emphasis("\\*not\\* _under_"), "*not* <u>under</u>");
+    }
+
+    #[test]
+    fn reports_cues_without_dialogue() {
+        let (screenplay, report) = parse("INT. ROOM\n\nJOHN\n(beat)\n");
+        assert!(screenplay.scenes.iter().all(|scene| scene.lines.is_empty()));
+        assert_eq!(report.warnings[0].line, 3);
+        assert_eq!(report.errors[0].message, "no dialogue found");
+    }
+}
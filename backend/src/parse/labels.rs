@@ -0,0 +1,124 @@
+use super::{markup, Sub};
+
+// longest label we take for a name, anything longer is most likely a sentence
+const MAX_LABEL_WORDS: usize = 4;
+
+/// Moves speaker labels like "SIR JOHN: Where were you?" out of the text and into the
+/// speaker of the cue.
+///
+/// Only labels written in capitals are taken, so a sentence like "Note: ..." stays as it
+/// is. A speaker the cue already has, like a WebVTT voice, wins over the label.
+pub fn extract(subs: Vec<Sub>) -> Vec<Sub> {
+    subs.into_iter()
+        .map(|sub| match split_label(&sub.text) {
+            Some((label, text)) => Sub {
+                speaker: sub.speaker.or_else(|| Some(title_case(&label))),
+                text: text.to_string(),
+                ..sub
+            },
+            None => sub,
+        })
+        .collect()
+}
+
+fn split_label(text: &str) -> Option<(String, &str)> {
+    let (label, text) = text.split_once(':')?;
+    let label = markup::strip(label);
+    let text = text.trim_start();
+
+    let words = label.split_whitespace().count();
+    let is_name = label.chars().any(char::is_alphabetic)
+        && label
+            .chars()
+            .all(|c| c.is_uppercase() || c.is_ascii_digit() || " .'-".contains(c))
+        && (1..=MAX_LABEL_WORDS).contains(&words);
+
+    (is_name && !markup::strip(text).is_empty()).then_some((label, text))
+}
+
+/// "SIR JOHN" becomes "Sir John", which is how characters are usually named.
+pub fn title_case(label: &str) -> String {
+    label
+        .split_whitespace()
+        .map(|word| {
+            let mut chars = word.chars();
+            match chars.next() {
+                Some(first) => first
+                    .to_uppercase()
+                    .chain(chars.flat_map(char::to_lowercase))
+                    .collect(),
+                None => String::new(),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::SubKind;
+
+    fn sub(text: &str, speaker: Option<&str>) -> Sub {
+        Sub {
+            kind: SubKind::Dialogue,
+            index: None,
+            line: 1,
+            start: 0,
+            end: 1000,
+            text: text.to_string(),
+            speaker: speaker.map(String::from),
+            style: None,
+            markup: Vec::new(),
+        }
+    }
+
+    fn extracted(text: &str, speaker: Option<&str>) -> (Option<String>, String) {
+        let sub = extract(vec![sub(text, speaker)]).remove(0);
+        (sub.speaker, sub.text)
+    }
+
+    #[test]
+    fn takes_capital_labels() {
+        assert_eq!(
+            extracted("SIR JOHN: Where were you?", None),
+            (Some("Sir John".to_string()), "Where were you?".to_string())
+        );
+        assert_eq!(
+            extracted("<i>O'BRIEN:</i> Yes.", None),
+            (Some("O'brien".to_string()), "</i> Yes.".to_string())
+        );
+        assert_eq!(
+            extracted("GUARD 2: Halt!", None),
+            (Some("Guard 2".to_string()), "Halt!".to_string())
+        );
+    }
+
+    #[test]
+    fn leaves_other_colons_alone() {
+        let texts = [
+            "Note: this is a sentence.",
+            "THE ONE WHO KNOWS TOO MUCH: long",
+            "12: numbers only",
+            "WAIT:",
+            "At 10:30 we leave.",
+        ];
+        for text in texts {
+            assert_eq!(extracted(text, None), (None, text.to_string()));
+        }
+    }
+
+    #[test]
+    fn prefers_the_existing_speaker() {
+        assert_eq!(
+            extracted("JOHN: Hi.", Some("Voice")),
+            (Some("Voice".to_string()), "Hi.".to_string())
+        );
+    }
+
+    #[test]
+    fn title_cases_names() {
+        assert_eq!(title_case("SIR  JOHN"), "Sir John");
+        assert_eq!(title_case("ÉLODIE"), "Élodie");
+    }
+}
@@ -0,0 +1,407 @@
+use async_graphql::{Enum, SimpleObject};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum, sqlx::Type)]
+#[sqlx(type_name = "markup_kind", rename_all = "lowercase")]
+pub enum MarkupKind {
+    Italic,
+    Bold,
+    Underline,
+    Color,
+    /// Where the text is placed on screen, the value is a numpad position like "an8".
+    Position,
+}
+
+/// A formatting span on the text of a sentence.
+#[derive(Debug, Clone, SimpleObject)]
+pub struct Markup {
+    pub kind: MarkupKind,
+    /// First character of the span, counted in characters of the plain text.
+    pub start_char: i64,
+    /// Character after the last one of the span.
+    pub end_char: i64,
+    /// The color for color spans and the position for position spans.
+    pub value: Option<String>,
+}
+
+/// Removes formatting tags from the text and returns them as spans on the plain text.
+///
+/// Understands the html like tags of srt and WebVTT (`<i>`, `<b>`, `<u>`,
+/// `<font color="...">`) and the override blocks of ASS (`{\i1}`, `{\an8}`, `{\c&H00FFFF&}`).
+/// Tags that are never closed run to the end of the text and closing tags without an
+/// opening one start at the beginning, which is what a cue split in the middle of a tag
+/// leaves behind. Other tags are dropped.
+pub fn extract(text: &str) -> (String, Vec<Markup>) {
+    let mut plain = String::with_capacity(text.len());
+    let mut len = 0;
+    let mut open: Vec<(MarkupKind, usize, Option<String>)> = Vec::new();
+    let mut spans: Vec<(MarkupKind, usize, usize, Option<String>)> = Vec::new();
+
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        let tag = match c {
+            '<' if is_html_tag(rest) => rest.find('>').map(|close| (&rest[1..close], close)),
+            '{' if rest.starts_with("{\\") => rest.find('}').map(|close| (&rest[1..close], close)),
+            _ => None,
+        };
+        let (tag, close) = match tag {
+            Some(tag) => tag,
+            None => {
+                plain.push(c);
+                len += 1;
+                rest = &rest[c.len_utf8()..];
+                continue;
+            }
+        };
+
+        let changes = match c {
+            '<' => html_tag(tag),
+            _ => override_block(tag),
+        };
+        for change in changes {
+            match change {
+                Change::Open(kind, value) => open.push((kind, len, value)),
+                Change::Close(kind) => match open.iter().rposition(|(open, ..)| *open == kind) {
+                    Some(i) => {
+                        let (kind, start, value) = open.remove(i);
+                        spans.push((kind, start, len, value));
+                    }
+                    None => spans.push((kind, 0, len, None)),
+                },
+                Change::Position(value) => {
+                    spans.retain(|(kind, ..)| *kind != MarkupKind::Position);
+                    spans.push((MarkupKind::Position, 0, usize::MAX, Some(value)));
+                }
+            }
+        }
+        rest = &rest[close + 1..];
+    }
+    for (kind, start, value) in open {
+        spans.push((kind, start, len, value));
+    }
+
+    // the text is trimmed, so the spans have to move with it
+    let leading = plain.chars().take_while(|c| c.is_whitespace()).count();
+    let trimmed = plain.trim().to_string();
+    let len = trimmed.chars().count();
+
+    let mut markup: Vec<Markup> = spans
+        .into_iter()
+        .map(|(kind, start, end, value)| Markup {
+            kind,
+            start_char: start.saturating_sub(leading).min(len) as i64,
+            end_char: end.saturating_sub(leading).min(len) as i64,
+            value,
+        })
+        .filter(|span| span.kind == MarkupKind::Position || span.start_char < span.end_char)
+        .collect();
+    markup.sort_by_key(|span| (span.start_char, span.end_char));
+
+    (trimmed, markup)
+}
+
+/// The text without any formatting tags.
+pub fn strip(text: &str) -> String {
+    extract(text).0
+}
+
+/// Puts the spans back into the plain text as srt tags, the reverse of [`extract`].
+///
+/// The position becomes an `{\anN}` block in front, which most players read in srt files
+/// as well. Spans that overlap without nesting are closed and opened again so the tags
+/// stay balanced.
+pub fn render(text: &str, markup: &[Markup]) -> String {
+    let mut rendered = String::with_capacity(text.len());
+    let position = markup
+        .iter()
+        .find(|span| span.kind == MarkupKind::Position)
+        .and_then(|span| span.value.as_deref());
+    if let Some(position) = position {
+        rendered.push_str(&format!("{{\\{}}}", position));
+    }
+
+    let spans: Vec<&Markup> = markup
+        .iter()
+        .filter(|span| span.start_char < span.end_char)
+        .filter(|span| match span.kind {
+            MarkupKind::Position => false,
+            MarkupKind::Color => span.value.is_some(),
+            _ => true,
+        })
+        .collect();
+    let chars: Vec<char> = text.chars().collect();
+    let mut open: Vec<&Markup> = Vec::new();
+    for i in 0..=chars.len() as i64 {
+        // spans opened after an ending one have to be closed first and opened again
+        if let Some(first) = open.iter().position(|span| span.end_char <= i) {
+            let closed: Vec<&Markup> = open.drain(first..).collect();
+            for span in closed.iter().rev() {
+                rendered.push_str(closing_tag(span.kind));
+            }
+            for span in closed.into_iter().filter(|span| span.end_char > i) {
+                rendered.push_str(&opening_tag(span));
+                open.push(span);
+            }
+        }
+        for span in spans.iter().filter(|span| span.start_char == i) {
+            rendered.push_str(&opening_tag(span));
+            open.push(span);
+        }
+        if let Some(c) = chars.get(i as usize) {
+            rendered.push(*c);
+        }
+    }
+    rendered
+}
+
+fn opening_tag(span: &Markup) -> String {
+    match span.kind {
+        MarkupKind::Italic => "<i>".to_string(),
+        MarkupKind::Bold => "<b>".to_string(),
+        MarkupKind::Underline => "<u>".to_string(),
+        MarkupKind::Color => format!(
+            "<font color=\"{}\">",
+            span.value.as_deref().unwrap_or_default()
+        ),
+        MarkupKind::Position => String::new(),
+    }
+}
+
+fn closing_tag(kind: MarkupKind) -> &'static str {
+    match kind {
+        MarkupKind::Italic => "</i>",
+        MarkupKind::Bold => "</b>",
+        MarkupKind::Underline => "</u>",
+        MarkupKind::Color => "</font>",
+        MarkupKind::Position => "",
+    }
+}
+
+/// How many bytes of formatting tags the text starts with.
+pub fn leading_tags(text: &str) -> usize {
+    let mut len = 0;
+    loop {
+        let rest = &text[len..];
+        let close = match rest.chars().next() {
+            Some('<') if is_html_tag(rest) => rest.find('>'),
+            Some('{') if rest.starts_with("{\\") => rest.find('}'),
+            _ => None,
+        };
+        match close {
+            Some(close) => len += close + 1,
+            None => return len,
+        }
+    }
+}
+
+// "<i>" and "</i>" are tags, "< 5" isn't
+fn is_html_tag(text: &str) -> bool {
+    text[1..].starts_with(|c: char| c.is_ascii_alphabetic() || c == '/')
+}
+
+enum Change {
+    Open(MarkupKind, Option<String>),
+    Close(MarkupKind),
+    Position(String),
+}
+
+// <i>, </i>, <font color="#ffffff"> and friends
+fn html_tag(tag: &str) -> Vec<Change> {
+    let (closing, tag) = match tag.strip_prefix('/') {
+        Some(tag) => (true, tag),
+        None => (false, tag),
+    };
+    let name = tag
+        .split(|c: char| c.is_whitespace() || c == '.')
+        .next()
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+    let kind = match name.as_str() {
+        "i" => MarkupKind::Italic,
+        "b" => MarkupKind::Bold,
+        "u" => MarkupKind::Underline,
+        "font" => MarkupKind::Color,
+        _ => return Vec::new(),
+    };
+
+    if closing {
+        return vec![Change::Close(kind)];
+    }
+    if kind != MarkupKind::Color {
+        return vec![Change::Open(kind, None)];
+    }
+    match attribute(tag, "color") {
+        Some(color) => vec![Change::Open(kind, Some(color))],
+        None => Vec::new(),
+    }
+}
+
+fn attribute(tag: &str, name: &str) -> Option<String> {
+    let lower = tag.to_ascii_lowercase();
+    let start = lower.find(&format!("{}=", name))? + name.len() + 1;
+    let value = tag[start..].trim_start_matches(['"', '\'']);
+    let end = value
+        .find(|c: char| c == '"' || c == '\'' || c.is_whitespace())
+        .unwrap_or(value.len());
+    Some(value[..end].to_string()).filter(|value| !value.is_empty())
+}
+
+// {\i1\an8\c&H00FFFF&} and friends
+fn override_block(block: &str) -> Vec<Change> {
+    block
+        .split('\\')
+        .filter_map(|tag| {
+            let tag = tag.trim();
+            if let Some(position) = tag.strip_prefix("an") {
+                return position
+                    .parse::<u8>()
+                    .ok()
+                    .filter(|position| (1..=9).contains(position))
+                    .map(|position| Change::Position(format!("an{}", position)));
+            }
+            if let Some(position) = tag.strip_prefix('a') {
+                // the legacy ssa alignment, 1-3 bottom, 5-7 top and 9-11 middle
+                let position = match position.parse::<u8>().ok()? {
+                    position @ 1..=3 => position,
+                    position @ 5..=7 => position + 2,
+                    position @ 9..=11 => position - 5,
+                    _ => return None,
+                };
+                return Some(Change::Position(format!("an{}", position)));
+            }
+            if let Some(color) = tag.strip_prefix("1c").or_else(|| tag.strip_prefix('c')) {
+                return Some(match ass_color(color) {
+                    Some(color) => Change::Open(MarkupKind::Color, Some(color)),
+                    None => Change::Close(MarkupKind::Color),
+                });
+            }
+
+            let kind = match tag.chars().next()? {
+                'i' => MarkupKind::Italic,
+                'b' => MarkupKind::Bold,
+                'u' => MarkupKind::Underline,
+                _ => return None,
+            };
+            // \b also takes font weights like \b700
+            match tag[1..].parse::<u32>().ok()? {
+                0 => Some(Change::Close(kind)),
+                _ => Some(Change::Open(kind, None)),
+            }
+        })
+        .collect()
+}
+
+// ass colors are &HBBGGRR&, html colors #RRGGBB
+fn ass_color(color: &str) -> Option<String> {
+    let hex = color.trim_matches('&').trim_start_matches(['H', 'h']);
+    let hex = format!("{:0>6}", hex);
+    if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    Some(format!("#{}{}{}", &hex[4..6], &hex[2..4], &hex[0..2]).to_ascii_lowercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn spans(markup: &[Markup]) -> Vec<(MarkupKind, i64, i64, Option<&str>)> {
+        markup
+            .iter()
+            .map(|span| {
+                (
+                    span.kind,
+                    span.start_char,
+                    span.end_char,
+                    span.value.as_deref(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn extracts_html_tags() {
+        let (text, markup) =
+            extract("<i>Où</i> est <font color=\"#FF0000\">le <b>café</b></font>?");
+        assert_eq!(text, "Où est le café?");
+        assert_eq!(
+            spans(&markup),
+            vec![
+                (MarkupKind::Italic, 0, 2, None),
+                (MarkupKind::Color, 7, 14, Some("#FF0000")),
+                (MarkupKind::Bold, 10, 14, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn extracts_ass_override_blocks() {
+        let (text, markup) = extract("{\\an8\\c&H00FFFF&}Look {\\i1}up{\\i0}!");
+        assert_eq!(text, "Look up!");
+        assert_eq!(
+            spans(&markup),
+            vec![
+                (MarkupKind::Position, 0, 8, Some("an8")),
+                (MarkupKind::Color, 0, 8, Some("#ffff00")),
+                (MarkupKind::Italic, 5, 7, None),
+            ]
+        );
+        let (_, markup) = extract("{\\a6}Legacy");
+        assert_eq!(
+            spans(&markup),
+            vec![(MarkupKind::Position, 0, 6, Some("an8"))]
+        );
+    }
+
+    #[test]
+    fn counts_offsets_in_characters_of_the_trimmed_text() {
+        let (text, markup) = extract("  <u>ñandú</u> ");
+        assert_eq!(text, "ñandú");
+        assert_eq!(spans(&markup), vec![(MarkupKind::Underline, 0, 5, None)]);
+    }
+
+    #[test]
+    fn repairs_unbalanced_tags() {
+        let (text, markup) = extract("was split</i> in <b>two");
+        assert_eq!(text, "was split in two");
+        assert_eq!(
+            spans(&markup),
+            vec![
+                (MarkupKind::Italic, 0, 9, None),
+                (MarkupKind::Bold, 13, 16, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_text_that_only_looks_like_tags() {
+        let (text, markup) = extract("1 < 2 and <unknown>x</unknown> {not a tag}");
+        assert_eq!(text, "1 < 2 and x {not a tag}");
+        assert!(markup.is_empty());
+    }
+
+    #[test]
+    fn renders_spans_as_tags() {
+        for text in [
+            "<i>Où</i> est <font color=\"#ff0000\">le <b>café</b></font>?",
+            "{\\an8}Look <i>up</i>!",
+        ] {
+            let (plain, markup) = extract(text);
+            assert_eq!(render(&plain, &markup), text);
+        }
+    }
+
+    #[test]
+    fn rebalances_overlapping_spans() {
+        let (plain, markup) = extract("<b>one <i>two</b> three</i>");
+        assert_eq!(
+            render(&plain, &markup),
+            "<b>one <i>two</i></b><i> three</i>"
+        );
+    }
+
+    #[test]
+    fn measures_leading_tags() {
+        assert_eq!(leading_tags("<i>{\\an8}- Hi"), 9);
+        assert_eq!(leading_tags("- <i>Hi"), 0);
+    }
+}
@@ -0,0 +1,340 @@
+use super::{exact_framerate, ParseReport, Sub, SubKind};
+
+// what most MicroDVD files are made for when neither the caller nor the file tell
+const DEFAULT_FRAMERATE: f64 = 23.976;
+
+/// Parses a decoded MicroDVD file like `{1025}{1100}Hello|world` into its cues.
+///
+/// Timing is in frames, so it is converted with the given framerate, the one a first cue
+/// like `{1}{1}23.976` declares, or 23.976, a framerate that isn't positive is ignored with
+/// a warning. Lines are separated by `|`. The italic, bold, underline and color control
+/// codes become tags, other control codes are dropped.
+pub fn parse(text: &str, framerate: Option<f64>) -> (Vec<Sub>, ParseReport) {
+    let mut report = ParseReport::default();
+
+    let text = text.trim_start_matches('\u{feff}');
+    let text = text.replace("\r\n", "\n").replace('\r', "\n");
+
+    let mut framerate = match framerate {
+        Some(framerate) if !is_valid_framerate(framerate) => {
+            report.warning(1, format!("invalid framerate {}, ignored", framerate));
+            None
+        }
+        framerate => framerate.map(exact_framerate),
+    };
+    let mut cues = Vec::new();
+    for (i, line) in text.split('\n').enumerate() {
+        let line_number = i + 1;
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let (start, end, text) = match parse_frames(line) {
+            Some(cue) => cue,
+            None => {
+                report.error(line_number, format!("invalid cue '{}', skipped", line));
+                continue;
+            }
+        };
+
+        // a cue at frame 1 with only a number in it declares the framerate
+        if cues.is_empty() && start <= 1 && end.is_some_and(|end| end <= 1) {
+            if let Ok(declared) = text.trim().parse::<f64>() {
+                if !is_valid_framerate(declared) {
+                    report.warning(
+                        line_number,
+                        format!("invalid framerate {} declared, ignored", declared),
+                    );
+                } else if framerate.is_none() {
+                    framerate = Some(exact_framerate(declared));
+                }
+                continue;
+            }
+        }
+
+        cues.push((line_number, start, end, text));
+    }
+
+    let framerate = match framerate {
+        Some(framerate) => framerate,
+        None => {
+            report.warning(
+                1,
+                format!("framerate unknown, assuming {}", DEFAULT_FRAMERATE),
+            );
+            exact_framerate(DEFAULT_FRAMERATE)
+        }
+    };
+    let ms = |frame: i64| (frame as f64 * 1000.0 / framerate).round() as i64;
+
+    let mut subs = Vec::with_capacity(cues.len());
+    for (n, (line, start, end, text)) in cues.iter().enumerate() {
+        // an empty end frame means the cue lasts until the next one
+        let end = match end {
+            Some(end) => *end,
+            None => match cues.get(n + 1) {
+                Some((_, next, _, _)) => *next,
+                None => {
+                    report.warning(*line, "last cue has no end frame, ends at its start");
+                    *start
+                }
+            },
+        };
+        let end = if end < *start {
+            report.warning(*line, "cue ends before it starts, end set to start");
+            *start
+        } else {
+            end
+        };
+
+        let text = tagged_text(text);
+        if text.trim().is_empty() {
+            report.warning(*line, "cue has no text, cue skipped");
+            continue;
+        }
+
+        subs.push(Sub {
+            kind: SubKind::Dialogue,
+            index: None,
+            line: *line,
+            start: ms(*start),
+            end: ms(end),
+            text,
+            speaker: None,
+            style: None,
+            markup: Vec::new(),
+        });
+    }
+
+    (subs, report)
+}
+
+/// Whether the first line of the text looks like a MicroDVD cue.
+pub fn sniff(text: &str) -> bool {
+    let first_line = text
+        .trim_start_matches('\u{feff}')
+        .trim_start()
+        .lines()
+        .next();
+    first_line.and_then(parse_frames).is_some()
+}
+
+// frames can only be turned into times with a positive framerate
+fn is_valid_framerate(framerate: f64) -> bool {
+    framerate.is_finite() && framerate > 0.0
+}
+
+// splits "{1025}{1100}text" into the frames and the text, the end frame may be empty
+fn parse_frames(line: &str) -> Option<(i64, Option<i64>, &str)> {
+    let rest = line.strip_prefix('{')?;
+    let (start, rest) = rest.split_once('}')?;
+    let rest = rest.strip_prefix('{')?;
+    let (end, text) = rest.split_once('}')?;
+
+    let start = start.trim().parse().ok()?;
+    let end = match end.trim() {
+        "" => None,
+        end => Some(end.parse().ok()?),
+    };
+    Some((start, end, text))
+}
+
+// turns "{Y:i}first|{c:$0000FF}second" into "<i>first\n<font color="#ff0000">second</font></i>"
+fn tagged_text(text: &str) -> String {
+    let mut lines = text.split('|');
+    let mut text = String::new();
+    let mut whole_cue = Vec::new();
+
+    for (n, line) in lines.by_ref().enumerate() {
+        let (codes, line) = control_codes(line);
+        let mut this_line = Vec::new();
+        for (scope, tag) in codes {
+            // upper case codes apply to the whole cue, lower case ones to their line
+            if scope.is_ascii_uppercase() {
+                text.push_str(&tag.open);
+                whole_cue.push(tag.close);
+            } else {
+                this_line.push(tag);
+            }
+        }
+
+        if n > 0 {
+            text.push('\n');
+        }
+        for tag in &this_line {
+            text.push_str(&tag.open);
+        }
+        text.push_str(line.trim());
+        for tag in this_line.iter().rev() {
+            text.push_str(tag.close);
+        }
+    }
+    for close in whole_cue.iter().rev() {
+        text.push_str(close);
+    }
+    text
+}
+
+struct Tag {
+    open: String,
+    close: &'static str,
+}
+
+// takes the control codes like {y:i} or {C:$BBGGRR} off the start of a line
+fn control_codes(line: &str) -> (Vec<(char, Tag)>, &str) {
+    let mut codes = Vec::new();
+    let mut rest = line.trim_start();
+    while let Some(code) = rest.strip_prefix('{') {
+        let Some((code, after)) = code.split_once('}') else {
+            break;
+        };
+        let Some((scope, value)) = code.split_once(':') else {
+            break;
+        };
+        let mut scope_chars = scope.chars();
+        let (Some(scope), None) = (scope_chars.next(), scope_chars.next()) else {
+            break;
+        };
+        rest = after.trim_start();
+
+        let tags = match scope.to_ascii_lowercase() {
+            'y' => value
+                .split(',')
+                .filter_map(|style| match style.trim().to_ascii_lowercase().as_str() {
+                    "i" => Some(("<i>", "</i>")),
+                    "b" => Some(("<b>", "</b>")),
+                    "u" => Some(("<u>", "</u>")),
+                    _ => None,
+                })
+                .map(|(open, close)| Tag {
+                    open: open.to_string(),
+                    close,
+                })
+                .collect(),
+            'c' => color(value)
+                .map(|color| Tag {
+                    open: format!("<font color=\"{}\">", color),
+                    close: "</font>",
+                })
+                .into_iter()
+                .collect(),
+            // fonts, sizes and positions aren't kept
+            _ => Vec::new(),
+        };
+        codes.extend(tags.into_iter().map(|tag| (scope, tag)));
+    }
+    (codes, rest)
+}
+
+// MicroDVD colors are $BBGGRR, html colors #RRGGBB
+fn color(value: &str) -> Option<String> {
+    let hex = value.trim().trim_start_matches('$');
+    if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    Some(format!("#{}{}{}", &hex[4..6], &hex[2..4], &hex[0..2]).to_ascii_lowercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uses_the_declared_framerate() {
+        let (subs, report) = parse("{1}{1}25\n{25}{50}Hello|world\n{75}{100}Bye\n", None);
+        assert!(report.warnings.is_empty() && report.errors.is_empty());
+        let cues: Vec<(usize, i64, i64, &str)> = subs
+            .iter()
+            .map(|sub| (sub.line, sub.start, sub.end, sub.text.as_str()))
+            .collect();
+        assert_eq!(
+            cues,
+            vec![(2, 1000, 2000, "Hello\nworld"), (3, 3000, 4000, "Bye")]
+        );
+    }
+
+    #[test]
+    fn prefers_the_given_framerate() {
+        let (subs, report) = parse("{1}{1}25\n{50}{100}Hello\n", Some(50.0));
+        assert!(report.warnings.is_empty());
+        assert_eq!((subs[0].start, subs[0].end), (1000, 2000));
+    }
+
+    #[test]
+    fn falls_back_to_ntsc_film() {
+        let (subs, report) = parse("{24}{48}Hello\n", None);
+        assert_eq!(
+            report.warnings[0].message,
+            "framerate unknown, assuming 23.976"
+        );
+        assert_eq!((subs[0].start, subs[0].end), (1001, 2002));
+    }
+
+    #[test]
+    fn ignores_invalid_framerates() {
+        let (subs, report) = parse("{24}{48}Hello\n", Some(f64::NAN));
+        let warnings: Vec<&str> = report
+            .warnings
+            .iter()
+            .map(|issue| issue.message.as_str())
+            .collect();
+        assert_eq!(
+            warnings,
+            vec![
+                "invalid framerate NaN, ignored",
+                "framerate unknown, assuming 23.976"
+            ]
+        );
+        assert_eq!((subs[0].start, subs[0].end), (1001, 2002));
+
+        let (subs, report) = parse("{1}{1}0\n{25}{50}Hello\n", Some(-25.0));
+        assert_eq!(report.warnings.len(), 3);
+        assert_eq!(report.warnings[1].line, 1);
+        assert_eq!(
+            report.warnings[1].message,
+            "invalid framerate 0 declared, ignored"
+        );
+        assert_eq!(subs.len(), 1);
+        assert!(subs[0].end > subs[0].start);
+
+        let (_, report) = parse("{1}{1}inf\n{25}{50}Hello\n", Some(25.0));
+        assert_eq!(
+            report.warnings[0].message,
+            "invalid framerate inf declared, ignored"
+        );
+    }
+
+    #[test]
+    fn repairs_end_frames() {
+        let (subs, report) = parse(
+            "{1}{1}25\n{25}{}Open end\n{50}{25}Backwards\nnot a cue\n{100}{}Last\n",
+            None,
+        );
+        let times: Vec<(i64, i64)> = subs.iter().map(|sub| (sub.start, sub.end)).collect();
+        assert_eq!(times, vec![(1000, 2000), (2000, 2000), (4000, 4000)]);
+        let warnings: Vec<usize> = report.warnings.iter().map(|issue| issue.line).collect();
+        assert_eq!(warnings, vec![3, 5]);
+        assert_eq!(report.errors[0].line, 4);
+    }
+
+    #[test]
+    fn turns_control_codes_into_tags() {
+        let (subs, _) = parse(
+            "{1}{1}25\n{0}{25}{Y:i}{c:$0000FF}First|{y:b,u}second|{f:Arial}third\n",
+            None,
+        );
+        assert_eq!(
+            subs[0].text,
+            "<i><font color=\"#ff0000\">First</font>\n<b><u>second</u></b>\nthird</i>"
+        );
+    }
+
+    #[test]
+    fn sniffs_cues() {
+        assert!(sniff("\u{feff}{0}{25}Hi"));
+        assert!(sniff("{0}{}Hi"));
+        assert!(!sniff("1\n00:00:01,000 --> 00:00:02,000"));
+        assert!(!sniff("{\"segments\": []}"));
+    }
+}
@@ -1,82 +1,401 @@
-use std::{env, fs::File, io::Read, time::Duration};
+use std::path::Path;
 
-use chrono::NaiveTime;
-use encoding_rs::WINDOWS_1252;
-use sqlx::postgres::PgPoolOptions;
+use async_graphql::{Enum, InputObject, SimpleObject};
+use encoding_rs::Encoding;
 
-#[derive(Debug)]
+pub mod ass;
+pub mod cornell;
+pub mod dialogue;
+pub mod encoding;
+pub mod events;
+pub mod fdx;
+pub mod fountain;
+pub mod labels;
+pub mod markup;
+pub mod microdvd;
+pub mod sbv;
+pub mod script;
+pub mod srt;
+pub mod transcript;
+pub mod ttml;
+pub mod vtt;
+
+use dialogue::DialogueTiming;
+use markup::Markup;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+pub enum SubtitleFormat {
+    Srt,
+    Vtt,
+    Ass,
+    /// Frame based `.sub` files, which need a framerate.
+    MicroDvd,
+    /// SubViewer `.sbv` files, as YouTube exports them.
+    Sbv,
+    /// TTML and DFXP, the XML formats of broadcasters.
+    Ttml,
+    /// JSON segments of a speech-to-text transcript, with diarized speakers.
+    Transcript,
+}
+
+impl SubtitleFormat {
+    /// Picks the format by the file extension and falls back to looking at the content.
+    pub fn detect(file_name: &str, text: &str) -> SubtitleFormat {
+        let extension = Path::new(file_name)
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(str::to_ascii_lowercase);
+        match extension.as_deref() {
+            Some("srt") => SubtitleFormat::Srt,
+            Some("vtt") => SubtitleFormat::Vtt,
+            Some("ass" | "ssa") => SubtitleFormat::Ass,
+            // SubViewer 2 files share the extension with MicroDVD
+            Some("sub") if !microdvd::sniff(text) && text.contains("[INFORMATION]") => {
+                SubtitleFormat::Sbv
+            }
+            Some("sub") => SubtitleFormat::MicroDvd,
+            Some("sbv") => SubtitleFormat::Sbv,
+            Some("ttml" | "dfxp" | "xml") => SubtitleFormat::Ttml,
+            Some("json") => SubtitleFormat::Transcript,
+            _ => {
+                let text = text.trim_start_matches('\u{feff}').trim_start();
+                let first_line = text.lines().next().unwrap_or_default().trim();
+                if text.starts_with("WEBVTT") {
+                    SubtitleFormat::Vtt
+                } else if text.starts_with("[Script Info]") {
+                    SubtitleFormat::Ass
+                } else if ttml::sniff(text) {
+                    SubtitleFormat::Ttml
+                } else if microdvd::sniff(text) {
+                    SubtitleFormat::MicroDvd
+                } else if transcript::sniff(text) {
+                    SubtitleFormat::Transcript
+                } else if sbv::is_timing(first_line) {
+                    SubtitleFormat::Sbv
+                } else {
+                    SubtitleFormat::Srt
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+pub enum SubKind {
+    Dialogue,
+    /// A non-dialogue annotation like "[door slams]".
+    SoundEvent,
+}
+
+/// A cue of a subtitle file, before it becomes a sentence or sound event.
+#[derive(Debug, Clone, SimpleObject)]
+#[graphql(name = "Cue")]
 pub struct Sub {
-    pub id: usize,
-    pub start: NaiveTime,
-    pub end: NaiveTime,
+    pub kind: SubKind,
+    /// The index the cue had in the file, if it had one.
+    pub index: Option<usize>,
+    /// The line the cue's timing is on, used for reporting.
+    pub line: usize,
+    /// Start of the cue in milliseconds.
+    #[graphql(name = "startMs")]
+    pub start: i64,
+    /// End of the cue in milliseconds.
+    #[graphql(name = "endMs")]
+    pub end: i64,
+    /// The text of the cue, parsers keep the line breaks so it can still be split up.
     pub text: String,
+    /// Who says the line, if the file tells.
+    pub speaker: Option<String>,
+    /// The style name the cue had in the file.
+    pub style: Option<String>,
+    /// Formatting spans on the text, only filled once the tags are taken out of the text.
+    pub markup: Vec<Markup>,
 }
 
-// write a function that parses a srt file with a windows encoding
-pub async fn parse_srt(path: &str, movie_id: i64) -> Vec<Sub> {
-    let mut file = File::open(path).unwrap();
-
-    // Read the bytes into a Vec<u8>
-    let mut bytes = Vec::new();
-    file.read_to_end(&mut bytes).unwrap();
+#[derive(Debug, Clone, SimpleObject)]
+pub struct ParseIssue {
+    pub line: usize,
+    pub message: String,
+}
 
-    // Choose the encoding
-    let encoding = WINDOWS_1252; // replace this with your custom encoding
+/// Everything a parser had to repair (warnings) or skip (errors).
+#[derive(Debug, Default, SimpleObject)]
+pub struct ParseReport {
+    pub warnings: Vec<ParseIssue>,
+    pub errors: Vec<ParseIssue>,
+}
 
-    // let db = create_client().await;
-    let db_url = env::var("DATABASE_URL").expect("DATABASE_URL env is not set");
+impl ParseReport {
+    pub fn warning(&mut self, line: usize, message: impl Into<String>) {
+        self.warnings.push(ParseIssue {
+            line,
+            message: message.into(),
+        });
+    }
 
-    let pool = PgPoolOptions::new()
-        .max_connections(5)
-        .connect(&db_url)
-        .await
-        .expect("Failed to connect to postgres");
+    pub fn error(&mut self, line: usize, message: impl Into<String>) {
+        self.errors.push(ParseIssue {
+            line,
+            message: message.into(),
+        });
+    }
+}
 
-    // Decode the bytes into a string
-    let (decoded_str, _, _) = encoding.decode(&bytes);
-    let mut subs = Vec::new();
+/// Options for turning cues into sentences.
+#[derive(Debug, Clone, InputObject)]
+pub struct ParseOptions {
+    /// Split cues with several dash prefixed lines into one sentence per dash.
+    #[graphql(default = true)]
+    pub split_dialogue: bool,
+    #[graphql(default_with = "DialogueTiming::Proportional")]
+    pub dialogue_timing: DialogueTiming,
+    /// Take speaker labels like "SIR JOHN: ..." out of the text and use them as speaker.
+    #[graphql(default = false)]
+    pub speaker_labels: bool,
+    /// Take bracketed annotations like "[door slams]" out of the dialogue into sound events.
+    #[graphql(default = true)]
+    pub sound_events: bool,
+    /// Frames per second for frame based formats like MicroDVD, taken from the file or
+    /// 23.976 when omitted.
+    pub framerate: Option<f64>,
+}
 
-    let transaction = pool.begin().await.unwrap();
-    for (i, sub) in decoded_str.split("\r\n\r\n").into_iter().enumerate() {
-        let mut lines = sub.lines();
-        let next = lines.next();
-        if next.is_none() {
-            continue;
+impl Default for ParseOptions {
+    fn default() -> Self {
+        ParseOptions {
+            split_dialogue: true,
+            dialogue_timing: DialogueTiming::Proportional,
+            speaker_labels: false,
+            sound_events: true,
+            framerate: None,
         }
-        let _id = next.unwrap().parse::<usize>().unwrap();
-
-        let times = lines.next().unwrap();
-        let mut times = times.split(" --> ");
-
-        let start_str = times.next().unwrap();
-        let start = parse_duration(start_str);
-        let end_str = times.next().unwrap();
-        let end = parse_duration(end_str);
-
-        let text = lines.collect::<Vec<_>>().join(" ");
-
-        sqlx::query!(
-            "INSERT INTO sentence (movie_id, start_time, end_time, text, position) VALUES ($1, $2, $3, $4, $5);",
-            movie_id,
-            start,
-            end,
-            text,
-            i as i64
-        ).execute(&pool).await.unwrap();
     }
-    transaction.commit().await.unwrap();
+}
+
+/// The result of parsing a subtitle file.
+#[derive(Debug)]
+pub struct Parsed {
+    pub subs: Vec<Sub>,
+    pub report: ParseReport,
+    pub format: SubtitleFormat,
+    /// The encoding the file was decoded with.
+    pub encoding: &'static Encoding,
+}
 
-    dbg!(&subs);
+/// Turns the raw bytes of a subtitle file into cues, without touching the database.
+///
+/// Format and encoding are detected unless they are given, see [`SubtitleFormat::detect`]
+/// and [`encoding::decode`].
+pub fn parse(
+    bytes: &[u8],
+    file_name: &str,
+    format: Option<SubtitleFormat>,
+    encoding: Option<&'static Encoding>,
+    options: &ParseOptions,
+) -> Parsed {
+    let (text, encoding) = encoding::decode(bytes, encoding);
+    let format = format.unwrap_or_else(|| SubtitleFormat::detect(file_name, &text));
+    let (subs, report) = match format {
+        SubtitleFormat::Srt => srt::parse(&text),
+        SubtitleFormat::Vtt => vtt::parse(&text),
+        SubtitleFormat::Ass => ass::parse(&text),
+        SubtitleFormat::MicroDvd => microdvd::parse(&text, options.framerate),
+        SubtitleFormat::Sbv => sbv::parse(&text),
+        SubtitleFormat::Ttml => ttml::parse(&text),
+        SubtitleFormat::Transcript => transcript::parse(&text),
+    };
 
-    subs
+    let subs = if options.split_dialogue {
+        dialogue::split(subs, options.dialogue_timing)
+    } else {
+        subs
+    };
+    let subs = if options.speaker_labels {
+        labels::extract(subs)
+    } else {
+        subs
+    };
+    let subs = if options.sound_events {
+        events::extract(subs)
+    } else {
+        subs
+    };
+    let subs = subs
+        .into_iter()
+        .map(|sub| {
+            let text = sub
+                .text
+                .lines()
+                .map(str::trim)
+                .collect::<Vec<_>>()
+                .join(" ");
+            let (text, markup) = markup::extract(&text);
+            Sub {
+                text,
+                markup,
+                ..sub
+            }
+        })
+        .filter(|sub| !sub.text.is_empty())
+        .collect();
+
+    Parsed {
+        subs,
+        report,
+        format,
+        encoding,
+    }
 }
 
-// write a function that takes a srt duration as param and returns a i64 in seconds
-fn parse_duration(duration: &str) -> i64 {
-    let mut times = duration.split(":");
-    let hours = times.next().unwrap().parse::<i64>().unwrap();
-    let minutes = times.next().unwrap().parse::<i64>().unwrap();
-    let mut times = times.next().unwrap().split(",");
-    let seconds = times.next().unwrap().parse::<i64>().unwrap();
-    hours * 60 * 60 + minutes * 60 + seconds
+/// Parses an srt or WebVTT timestamp like 00:01:34,800 into milliseconds.
+///
+/// Also accepts a dot as decimal separator, a missing hour part and fractions with
+/// fewer than three digits.
+pub fn parse_timestamp(timestamp: &str) -> Option<i64> {
+    let (clock, fraction) = match timestamp.split_once([',', '.']) {
+        Some((clock, fraction)) => (clock, fraction),
+        None => (timestamp, "0"),
+    };
+
+    let parts = clock
+        .split(':')
+        .map(|part| part.trim().parse::<i64>().ok())
+        .collect::<Option<Vec<_>>>()?;
+    let seconds = match parts[..] {
+        [hours, minutes, seconds] => (hours * 60 + minutes) * 60 + seconds,
+        [minutes, seconds] => minutes * 60 + seconds,
+        _ => return None,
+    };
+
+    let fraction = fraction.trim();
+    if fraction.is_empty() || !fraction.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let millis = format!("{:0<3}", &fraction[..fraction.len().min(3)])
+        .parse::<i64>()
+        .ok()?;
+
+    Some(seconds * 1000 + millis)
+}
+
+/// Turns a rounded NTSC framerate like 23.976 or 29.97 into its exact value, 24000/1001
+/// or 30000/1001, so long files don't drift. Other framerates are returned as they are.
+pub fn exact_framerate(fps: f64) -> f64 {
+    [24.0, 30.0, 60.0]
+        .into_iter()
+        .map(|rate| rate * 1000.0 / 1001.0)
+        .find(|exact| (fps - exact).abs() < 0.01)
+        .unwrap_or(fps)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_timestamps() {
+        assert_eq!(parse_timestamp("01:02:03,004"), Some(3_723_004));
+        assert_eq!(parse_timestamp("02:03.5"), Some(123_500));
+        assert_eq!(parse_timestamp("0:00:01.25"), Some(1250));
+        assert_eq!(parse_timestamp("00:00:01"), Some(1000));
+        assert_eq!(parse_timestamp("00:00:01,1234"), Some(1123));
+        assert_eq!(parse_timestamp("00:00:01,"), None);
+        assert_eq!(parse_timestamp("1"), None);
+        assert_eq!(parse_timestamp("aa:bb:cc,000"), None);
+    }
+
+    #[test]
+    fn makes_ntsc_framerates_exact() {
+        assert_eq!(exact_framerate(23.976), 24000.0 / 1001.0);
+        assert_eq!(exact_framerate(29.97), 30000.0 / 1001.0);
+        assert_eq!(exact_framerate(25.0), 25.0);
+    }
+
+    #[test]
+    fn detects_formats() {
+        let detect = SubtitleFormat::detect;
+        assert_eq!(detect("a.SRT", ""), SubtitleFormat::Srt);
+        assert_eq!(detect("a.ssa", ""), SubtitleFormat::Ass);
+        assert_eq!(detect("a.dfxp", ""), SubtitleFormat::Ttml);
+        assert_eq!(detect("a.sub", "{1}{25}Hi"), SubtitleFormat::MicroDvd);
+        assert_eq!(
+            detect("a.sub", "[INFORMATION]\n00:00:01.00,00:00:02.00\nHi"),
+            SubtitleFormat::Sbv
+        );
+        assert_eq!(detect("a", "\u{feff}WEBVTT\n"), SubtitleFormat::Vtt);
+        assert_eq!(detect("a", "[Script Info]\n"), SubtitleFormat::Ass);
+        assert_eq!(
+            detect(
+                "a",
+                "<?xml version=\"1.0\"?>\n<tt xmlns=\"http://www.w3.org/ns/ttml\">"
+            ),
+            SubtitleFormat::Ttml
+        );
+        assert_eq!(detect("a", "{1}{25}Hi"), SubtitleFormat::MicroDvd);
+        assert_eq!(
+            detect("a", "0:00:01.000,0:00:02.000\nHi"),
+            SubtitleFormat::Sbv
+        );
+        assert_eq!(
+            detect("a", "1\n00:00:01,000 --> 00:00:02,000\nHi"),
+            SubtitleFormat::Srt
+        );
+        assert_eq!(
+            detect("a", "[{\"start\": 1.0, \"end\": 2.0, \"text\": \"Hi\"}]"),
+            SubtitleFormat::Transcript
+        );
+        assert_eq!(
+            detect("a", "[door slams]\n\n1\n00:00:01,000 --> 00:00:02,000\nHi"),
+            SubtitleFormat::Srt
+        );
+    }
+
+    #[test]
+    fn turns_cues_into_sentences() {
+        let file =
+            "1\n00:00:00,000 --> 00:00:03,000\n- [door slams] WHO'S THERE?\n- JOHN: <i>Me.</i>\n";
+        let options = ParseOptions {
+            dialogue_timing: DialogueTiming::Share,
+            speaker_labels: true,
+            ..ParseOptions::default()
+        };
+        let parsed = parse(file.as_bytes(), "test.srt", None, None, &options);
+        assert_eq!(parsed.format, SubtitleFormat::Srt);
+        assert_eq!(parsed.encoding, encoding_rs::UTF_8);
+
+        let subs: Vec<(SubKind, &str, Option<&str>)> = parsed
+            .subs
+            .iter()
+            .map(|sub| (sub.kind, sub.text.as_str(), sub.speaker.as_deref()))
+            .collect();
+        assert_eq!(
+            subs,
+            vec![
+                (SubKind::SoundEvent, "door slams", None),
+                (SubKind::Dialogue, "WHO'S THERE?", None),
+                (SubKind::Dialogue, "Me.", Some("John")),
+            ]
+        );
+        assert_eq!(parsed.subs[2].markup.len(), 1);
+        assert_eq!(
+            (
+                parsed.subs[2].markup[0].start_char,
+                parsed.subs[2].markup[0].end_char
+            ),
+            (0, 3)
+        );
+    }
+
+    #[test]
+    fn joins_lines_without_splitting() {
+        let file = "1\n00:00:00,000 --> 00:00:03,000\n- One\n- Two\n";
+        let options = ParseOptions {
+            split_dialogue: false,
+            sound_events: false,
+            ..ParseOptions::default()
+        };
+        let parsed = parse(file.as_bytes(), "test.srt", None, None, &options);
+        assert_eq!(parsed.subs.len(), 1);
+        assert_eq!(parsed.subs[0].text, "- One - Two");
+    }
 }
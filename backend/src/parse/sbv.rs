@@ -0,0 +1,116 @@
+use super::{parse_timestamp, ParseReport, Sub, SubKind};
+
+/// Parses a decoded SubViewer file, as YouTube exports them, into its cues.
+///
+/// Cues are separated by blank lines and start with a timing line like
+/// `0:00:01.500,0:00:04.000`. SubViewer 2 headers like `[INFORMATION]` are skipped.
+pub fn parse(text: &str) -> (Vec<Sub>, ParseReport) {
+    let mut report = ParseReport::default();
+
+    let text = text.trim_start_matches('\u{feff}');
+    let text = text.replace("\r\n", "\n").replace('\r', "\n");
+    let lines: Vec<&str> = text.split('\n').map(str::trim).collect();
+
+    let mut subs = Vec::new();
+    let mut i = 0;
+    while i < lines.len() {
+        if lines[i].is_empty() || lines[i].starts_with('[') {
+            i += 1;
+            continue;
+        }
+
+        let line = i + 1;
+        let block_end = lines[i..]
+            .iter()
+            .position(|line| line.is_empty())
+            .map_or(lines.len(), |len| i + len);
+        let timing = lines[i];
+        let text_lines = &lines[i + 1..block_end];
+        i = block_end;
+
+        let (start, end) = match parse_timing(timing) {
+            Some(times) => times,
+            None => {
+                report.error(line, format!("invalid timing '{}', cue skipped", timing));
+                continue;
+            }
+        };
+        let end = if end < start {
+            report.warning(line, "cue ends before it starts, end set to start");
+            start
+        } else {
+            end
+        };
+
+        if text_lines.is_empty() {
+            report.warning(line, "cue has no text, cue skipped");
+            continue;
+        }
+
+        subs.push(Sub {
+            kind: SubKind::Dialogue,
+            index: None,
+            line,
+            start,
+            end,
+            // SubViewer 2 writes line breaks as [br]
+            text: text_lines.join("\n").replace("[br]", "\n"),
+            speaker: None,
+            style: None,
+            markup: Vec::new(),
+        });
+    }
+
+    if subs.is_empty() && report.errors.is_empty() {
+        report.error(1, "no cues found");
+    }
+    (subs, report)
+}
+
+/// Whether a line is an SBV timing line like `0:00:01.500,0:00:04.000`.
+pub fn is_timing(line: &str) -> bool {
+    parse_timing(line).is_some()
+}
+
+fn parse_timing(line: &str) -> Option<(i64, i64)> {
+    let (start, end) = line.split_once(',')?;
+    // a comma in a timestamp means this is an srt timing, not ours
+    if start.contains("-->") || end.contains(',') {
+        return None;
+    }
+    Some((parse_timestamp(start.trim())?, parse_timestamp(end.trim())?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_cues() {
+        let (subs, report) = parse(
+            "[INFORMATION]\n[TITLE]x\n\n0:00:01.500,0:00:04.000\nHello[br]there\nagain\n\n\
+            0:00:05.000,0:00:04.000\nBackwards\n\n0:00:06.000,soon\nSkipped\n\n0:00:07.000,0:00:08.000\n",
+        );
+        let cues: Vec<(usize, i64, i64, &str)> = subs
+            .iter()
+            .map(|sub| (sub.line, sub.start, sub.end, sub.text.as_str()))
+            .collect();
+        assert_eq!(
+            cues,
+            vec![
+                (4, 1500, 4000, "Hello\nthere\nagain"),
+                (8, 5000, 5000, "Backwards")
+            ]
+        );
+        let warnings: Vec<usize> = report.warnings.iter().map(|issue| issue.line).collect();
+        assert_eq!(warnings, vec![8, 14]);
+        assert_eq!(report.errors[0].line, 11);
+    }
+
+    #[test]
+    fn tells_timings_apart_from_srt() {
+        assert!(is_timing("0:00:01.500,0:00:04.000"));
+        assert!(!is_timing("00:00:01,500 --> 00:00:04,000"));
+        assert!(!is_timing("Well, hello"));
+    }
+}
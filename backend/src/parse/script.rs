@@ -0,0 +1,217 @@
+use std::path::Path;
+
+use async_graphql::Enum;
+use encoding_rs::Encoding;
+
+use super::{encoding, fdx, fountain, labels, markup::Markup, ParseReport};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+pub enum ScreenplayFormat {
+    Fountain,
+    /// Final Draft xml.
+    Fdx,
+}
+
+impl ScreenplayFormat {
+    /// Picks the format by the file extension and falls back to looking at the content.
+    pub fn detect(file_name: &str, text: &str) -> ScreenplayFormat {
+        let extension = Path::new(file_name)
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(str::to_ascii_lowercase);
+        match extension.as_deref() {
+            Some("fountain" | "spmd" | "txt") => ScreenplayFormat::Fountain,
+            Some("fdx") => ScreenplayFormat::Fdx,
+            _ if text.contains("<FinalDraft") => ScreenplayFormat::Fdx,
+            _ => ScreenplayFormat::Fountain,
+        }
+    }
+}
+
+/// A screenplay reduced to what the model knows about: scenes with their dialogue.
+#[derive(Debug, Default)]
+pub struct Screenplay {
+    pub scenes: Vec<ScriptScene>,
+}
+
+/// A scene of a screenplay. Dialogue before the first scene heading ends up in a scene
+/// without heading.
+#[derive(Debug, Default)]
+pub struct ScriptScene {
+    /// The full scene heading like "INT. CASTLE - NIGHT".
+    pub heading: Option<String>,
+    /// The location part of the heading like "CASTLE".
+    pub location: Option<String>,
+    pub lines: Vec<ScriptLine>,
+}
+
+/// A line of dialogue. A parenthetical in the middle of a speech starts a new line.
+#[derive(Debug)]
+pub struct ScriptLine {
+    /// The line the dialogue starts on, used for reporting.
+    pub line: usize,
+    /// The name of the character as it should be created, not the cue in capitals.
+    pub speaker: String,
+    pub parenthetical: Option<String>,
+    pub text: String,
+    pub markup: Vec<Markup>,
+}
+
+/// The result of parsing a screenplay file.
+#[derive(Debug)]
+pub struct ParsedScreenplay {
+    pub screenplay: Screenplay,
+    pub report: ParseReport,
+    pub format: ScreenplayFormat,
+    /// The encoding the file was decoded with.
+    pub encoding: &'static Encoding,
+}
+
+/// Turns the raw bytes of a screenplay into scenes and dialogue, without touching the
+/// database.
+pub fn parse(
+    bytes: &[u8],
+    file_name: &str,
+    format: Option<ScreenplayFormat>,
+    encoding: Option<&'static Encoding>,
+) -> ParsedScreenplay {
+    let (text, encoding) = encoding::decode(bytes, encoding);
+    let format = format.unwrap_or_else(|| ScreenplayFormat::detect(file_name, &text));
+    let (screenplay, report) = match format {
+        ScreenplayFormat::Fountain => fountain::parse(&text),
+        ScreenplayFormat::Fdx => fdx::parse(&text),
+    };
+    ParsedScreenplay {
+        screenplay,
+        report,
+        format,
+        encoding,
+    }
+}
+
+// words that end a scene heading and tell the time instead of the place
+const TIMES_OF_DAY: [&str; 12] = [
+    "DAY",
+    "NIGHT",
+    "MORNING",
+    "AFTERNOON",
+    "EVENING",
+    "DAWN",
+    "DUSK",
+    "CONTINUOUS",
+    "LATER",
+    "MOMENTS LATER",
+    "SAME",
+    "SAME TIME",
+];
+
+// prefixes of a scene heading, longest first so INT./EXT. wins over INT.
+const HEADING_PREFIXES: [&str; 7] = [
+    "INT./EXT", "INT/EXT", "EXT./INT", "I/E", "INT", "EXT", "EST",
+];
+
+/// Whether a line starts like a scene heading, with INT., EXT. and the like.
+pub fn is_heading(line: &str) -> bool {
+    let upper = line.trim().to_uppercase();
+    HEADING_PREFIXES.iter().any(|prefix| {
+        upper
+            .strip_prefix(prefix)
+            .is_some_and(|rest| rest.starts_with(['.', ' ']))
+    })
+}
+
+/// Takes the location out of a scene heading, "INT. CASTLE - HALL - NIGHT" is at
+/// "CASTLE - HALL". Forced headings without INT. or EXT. have no location.
+pub fn heading_location(heading: &str) -> Option<String> {
+    let heading = heading.trim();
+    let upper = heading.to_uppercase();
+    let prefix = HEADING_PREFIXES
+        .iter()
+        .find(|prefix| upper.starts_with(*prefix))?
+        .len();
+    let mut location = heading[prefix..].trim_start_matches(['.', ' ']).trim();
+
+    if let Some((place, time)) = location.rsplit_once(" - ") {
+        if TIMES_OF_DAY.contains(&time.trim().to_uppercase().as_str()) {
+            location = place.trim();
+        }
+    }
+
+    (!location.is_empty()).then(|| location.to_string())
+}
+
+/// Removes extensions like "(V.O.)" or "(CONT'D)" from a character cue.
+pub fn cue_name(cue: &str) -> String {
+    let name = match cue.find('(') {
+        Some(open) => &cue[..open],
+        None => cue,
+    };
+    name.trim().to_string()
+}
+
+/// The name a character cue stands for. Cues in capitals like "SIR JOHN" become
+/// "Sir John", names with lower case letters like "McCLANE" are kept as written.
+pub fn character_name(cue: &str) -> String {
+    let name = cue_name(cue);
+    if name.chars().any(char::is_lowercase) {
+        name
+    } else {
+        labels::title_case(&name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_formats() {
+        assert_eq!(
+            ScreenplayFormat::detect("a.fountain", ""),
+            ScreenplayFormat::Fountain
+        );
+        assert_eq!(ScreenplayFormat::detect("a.FDX", ""), ScreenplayFormat::Fdx);
+        assert_eq!(
+            ScreenplayFormat::detect("upload", "<?xml?><FinalDraft>"),
+            ScreenplayFormat::Fdx
+        );
+        assert_eq!(
+            ScreenplayFormat::detect("upload", "INT. ROOM"),
+            ScreenplayFormat::Fountain
+        );
+    }
+
+    #[test]
+    fn recognises_headings() {
+        assert!(is_heading("INT. CASTLE - NIGHT"));
+        assert!(is_heading("int/ext car"));
+        assert!(is_heading("I/E. CAR"));
+        assert!(!is_heading("INTERIOR DESIGN"));
+        assert!(!is_heading("Exterminate!"));
+    }
+
+    #[test]
+    fn takes_locations_from_headings() {
+        assert_eq!(
+            heading_location("INT. CASTLE - HALL - NIGHT").as_deref(),
+            Some("CASTLE - HALL")
+        );
+        assert_eq!(
+            heading_location("INT./EXT. CAR - MOMENTS LATER").as_deref(),
+            Some("CAR")
+        );
+        assert_eq!(
+            heading_location("EXT. ROAD - MILE 12").as_deref(),
+            Some("ROAD - MILE 12")
+        );
+        assert_eq!(heading_location("FLASHBACK"), None);
+        assert_eq!(heading_location("INT."), None);
+    }
+
+    #[test]
+    fn names_characters() {
+        assert_eq!(cue_name("SIR JOHN (V.O.) (CONT'D)"), "SIR JOHN");
+        assert_eq!(character_name("SIR JOHN (O.S.)"), "Sir John");
+        assert_eq!(character_name("McCLANE"), "McCLANE");
+    }
+}
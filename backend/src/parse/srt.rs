@@ -0,0 +1,292 @@
+use super::{markup, parse_timestamp, ParseReport, Sub, SubKind};
+
+/// Parses a decoded srt file into its cues.
+///
+/// Real world files are messy, so instead of failing on the first problem this repairs
+/// what it can and skips cues it can't make sense of. Everything it had to work around
+/// ends up in the returned report, together with the line it happened on.
+pub fn parse(text: &str) -> (Vec<Sub>, ParseReport) {
+    let mut report = ParseReport::default();
+
+    // normalise windows and old mac line endings and drop trailing whitespace
+    let text = text.trim_start_matches('\u{feff}');
+    let text = text.replace("\r\n", "\n").replace('\r', "\n");
+    let lines: Vec<&str> = text.split('\n').map(str::trim_end).collect();
+
+    let timings: Vec<usize> = lines
+        .iter()
+        .enumerate()
+        .filter(|(_, line)| line.contains("-->"))
+        .map(|(i, _)| i)
+        .collect();
+
+    if timings.is_empty() {
+        report.error(1, "no cues found");
+        return (Vec::new(), report);
+    }
+
+    let header_end = cue_start(&lines, timings[0]);
+    if let Some(i) = (0..header_end).find(|i| !lines[*i].trim().is_empty()) {
+        report.warning(i + 1, "ignored text before the first cue");
+    }
+
+    let mut subs: Vec<Sub> = Vec::new();
+    for (n, &timing) in timings.iter().enumerate() {
+        let line = timing + 1;
+        let text_end = timings
+            .get(n + 1)
+            .map(|next| cue_start(&lines, *next))
+            .unwrap_or(lines.len());
+
+        let index = parse_index(&lines, timing);
+        if index.is_none() {
+            report.warning(line, "cue has no index");
+        }
+
+        let (start, end) = match parse_timing(lines[timing]) {
+            Some(times) => times,
+            None => {
+                report.error(
+                    line,
+                    format!("invalid timing '{}', cue skipped", lines[timing]),
+                );
+                continue;
+            }
+        };
+
+        let end = if end < start {
+            report.warning(line, "cue ends before it starts, end set to start");
+            start
+        } else {
+            end
+        };
+
+        let mut text_lines = Vec::new();
+        let mut blank = None;
+        for (i, text_line) in lines.iter().enumerate().take(text_end).skip(timing + 1) {
+            let text_line = text_line.trim();
+            if text_line.is_empty() {
+                blank = blank.or(Some(i + 1));
+                continue;
+            }
+            if let Some(blank) = blank.take() {
+                if !text_lines.is_empty() {
+                    report.warning(blank, "blank line inside cue");
+                }
+            }
+            text_lines.push(text_line);
+        }
+
+        if text_lines.is_empty() {
+            report.warning(line, "cue has no text, cue skipped");
+            continue;
+        }
+
+        subs.push(Sub {
+            kind: SubKind::Dialogue,
+            index,
+            line,
+            start,
+            end,
+            text: text_lines.join("\n"),
+            speaker: None,
+            style: None,
+            markup: Vec::new(),
+        });
+    }
+
+    (subs, report)
+}
+
+// the line a cue starts on, which is the index line right above the timing if there is one
+fn cue_start(lines: &[&str], timing: usize) -> usize {
+    match timing.checked_sub(1) {
+        Some(above) if is_index(lines[above]) => above,
+        _ => timing,
+    }
+}
+
+fn parse_index(lines: &[&str], timing: usize) -> Option<usize> {
+    let above = lines.get(timing.checked_sub(1)?)?;
+    above.trim().parse().ok()
+}
+
+fn is_index(line: &str) -> bool {
+    let line = line.trim();
+    !line.is_empty() && line.chars().all(|c| c.is_ascii_digit())
+}
+
+// parses "00:01:34,800 --> 00:01:38,400" and ignores anything after the end time,
+// like the X1/Y1 coordinates some players write
+fn parse_timing(line: &str) -> Option<(i64, i64)> {
+    let (start, end) = line.split_once("-->")?;
+    let end = end.split_whitespace().next()?;
+    Some((parse_timestamp(start.trim())?, parse_timestamp(end)?))
+}
+
+/// Writes cues as an srt file, numbered from 1 in the order they are given.
+///
+/// The markup of a cue is written as tags and its speaker, if it has one, in front of the
+/// text like `Bianca: Can we make this quick?`. Lines end with `\r\n` like most srt files.
+pub fn write(subs: &[Sub]) -> String {
+    let mut output = String::new();
+    for (i, sub) in subs.iter().enumerate() {
+        let mut text = markup::render(&sub.text, &sub.markup);
+        if let Some(speaker) = &sub.speaker {
+            // a position block has to stay in front
+            let position = if text.starts_with("{\\") {
+                text.find('}').map_or(0, |close| close + 1)
+            } else {
+                0
+            };
+            text.insert_str(position, &format!("{}: ", speaker));
+        }
+        if i > 0 {
+            output.push_str("\r\n");
+        }
+        output.push_str(&format!(
+            "{}\r\n{} --> {}\r\n",
+            i + 1,
+            format_timestamp(sub.start),
+            format_timestamp(sub.end)
+        ));
+        for line in text.lines() {
+            output.push_str(line);
+            output.push_str("\r\n");
+        }
+    }
+    output
+}
+
+// 3723004 becomes 01:02:03,004
+fn format_timestamp(ms: i64) -> String {
+    let ms = ms.max(0);
+    format!(
+        "{:02}:{:02}:{:02},{:03}",
+        ms / 3_600_000,
+        ms / 60_000 % 60,
+        ms / 1000 % 60,
+        ms % 1000
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn issues(issues: &[crate::parse::ParseIssue]) -> Vec<(usize, &str)> {
+        issues
+            .iter()
+            .map(|issue| (issue.line, issue.message.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn parses_cues() {
+        let (subs, report) = parse(
+            "\u{feff}1\r\n00:00:01,000 --> 00:00:02,500\r\nHello\r\nthere\r\n\r\n\
+            2\r\n00:01:00.5 --> 00:01:02,000 X1:100 Y1:200\r\nBye\r\n",
+        );
+        assert!(report.warnings.is_empty() && report.errors.is_empty());
+        assert_eq!(subs.len(), 2);
+        assert_eq!(subs[0].index, Some(1));
+        assert_eq!((subs[0].start, subs[0].end), (1000, 2500));
+        assert_eq!(subs[0].text, "Hello\nthere");
+        assert_eq!(subs[0].line, 2);
+        assert_eq!((subs[1].start, subs[1].end), (60500, 62000));
+    }
+
+    #[test]
+    fn recovers_from_malformed_cues() {
+        let text = "garbage\n\
+            \n\
+            1\n\
+            00:00:01,000 --> 00:00:02,500\n\
+            Hello\n\
+            \n\
+            00:00:03,000 --> 00:00:04,000\n\
+            No index\n\
+            \n\
+            3\n\
+            00:00:05,000 --> nonsense\n\
+            Skipped\n\
+            \n\
+            4\n\
+            00:00:07,000 --> 00:00:06,000\n\
+            Backwards\n\
+            \n\
+            second paragraph\n";
+        let (subs, report) = parse(text);
+
+        let cues: Vec<(Option<usize>, usize, i64, i64, &str)> = subs
+            .iter()
+            .map(|sub| (sub.index, sub.line, sub.start, sub.end, sub.text.as_str()))
+            .collect();
+        assert_eq!(
+            cues,
+            vec![
+                (Some(1), 4, 1000, 2500, "Hello"),
+                (None, 7, 3000, 4000, "No index"),
+                (Some(4), 15, 7000, 7000, "Backwards\nsecond paragraph"),
+            ]
+        );
+        assert_eq!(
+            issues(&report.warnings),
+            vec![
+                (1, "ignored text before the first cue"),
+                (7, "cue has no index"),
+                (15, "cue ends before it starts, end set to start"),
+                (17, "blank line inside cue"),
+            ]
+        );
+        assert_eq!(
+            issues(&report.errors),
+            vec![(
+                11,
+                "invalid timing '00:00:05,000 --> nonsense', cue skipped"
+            )]
+        );
+    }
+
+    #[test]
+    fn reports_files_without_cues() {
+        let (subs, report) = parse("just some text\n");
+        assert!(subs.is_empty());
+        assert_eq!(issues(&report.errors), vec![(1, "no cues found")]);
+    }
+
+    #[test]
+    fn writes_cues_with_markup_and_speakers() {
+        let (mut subs, _) = parse("1\n00:00:01,000 --> 01:02:03,004\nHello\n");
+        subs[0].markup = markup::extract("<i>Hello</i>").1;
+        subs[0].speaker = Some("Bianca".to_string());
+        subs.push(Sub {
+            start: 3_723_005,
+            end: 3_724_000,
+            text: "Bye".to_string(),
+            speaker: None,
+            markup: Vec::new(),
+            ..subs[0].clone()
+        });
+
+        let written = write(&subs);
+        assert_eq!(
+            written,
+            "1\r\n00:00:01,000 --> 01:02:03,004\r\nBianca: <i>Hello</i>\r\n\r\n\
+            2\r\n01:02:03,005 --> 01:02:04,000\r\nBye\r\n"
+        );
+
+        let (parsed, report) = parse(&written);
+        assert!(report.warnings.is_empty() && report.errors.is_empty());
+        assert_eq!(parsed.len(), 2);
+        assert_eq!((parsed[1].start, parsed[1].end), (3_723_005, 3_724_000));
+    }
+
+    #[test]
+    fn keeps_the_position_in_front_of_the_speaker() {
+        let (mut subs, _) = parse("1\n00:00:01,000 --> 00:00:02,000\nUp here\n");
+        subs[0].markup = markup::extract("{\\an8}Up here").1;
+        subs[0].speaker = Some("Kat".to_string());
+        assert!(write(&subs).contains("{\\an8}Kat: Up here\r\n"));
+    }
+}
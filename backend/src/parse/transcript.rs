@@ -0,0 +1,216 @@
+use serde_json::Value;
+
+use super::{ParseReport, Sub, SubKind};
+
+/// Parses the JSON segments of a speech-to-text transcript into cues.
+///
+/// Takes either a list of segments or an object with a `segments` list, as written by
+/// Whisper and WhisperX, where `start` and `end` are in seconds. The whisper.cpp layout
+/// with a `transcription` list and `offsets` in milliseconds works as well. The diarized
+/// speaker label of a segment like `SPEAKER_01` becomes its speaker, or the label most of
+/// its words have. Issues are reported with the number of the segment instead of a line.
+pub fn parse(text: &str) -> (Vec<Sub>, ParseReport) {
+    let mut report = ParseReport::default();
+    let mut subs = Vec::new();
+
+    let json: Value = match serde_json::from_str(text.trim_start_matches('\u{feff}')) {
+        Ok(json) => json,
+        Err(error) => {
+            report.error(error.line(), format!("invalid json: {}", error));
+            return (subs, report);
+        }
+    };
+    let segments = match &json {
+        Value::Array(segments) => segments,
+        Value::Object(object) => match object
+            .get("segments")
+            .or_else(|| object.get("transcription"))
+        {
+            Some(Value::Array(segments)) => segments,
+            _ => {
+                report.error(1, "file has no segments");
+                return (subs, report);
+            }
+        },
+        _ => {
+            report.error(1, "file has no segments");
+            return (subs, report);
+        }
+    };
+
+    for (i, segment) in segments.iter().enumerate() {
+        let number = i + 1;
+        let (start, end) = match timing(segment) {
+            Some(times) => times,
+            None => {
+                report.error(number, "segment has no valid start and end, skipped");
+                continue;
+            }
+        };
+        let end = if end < start {
+            report.warning(number, "segment ends before it starts, end set to start");
+            start
+        } else {
+            end
+        };
+
+        let text = segment["text"].as_str().unwrap_or_default().trim();
+        if text.is_empty() {
+            report.warning(number, "segment has no text, skipped");
+            continue;
+        }
+
+        subs.push(Sub {
+            kind: SubKind::Dialogue,
+            index: Some(number),
+            line: number,
+            start,
+            end,
+            text: text.to_string(),
+            speaker: speaker(segment),
+            style: None,
+            markup: Vec::new(),
+        });
+    }
+
+    subs.sort_by_key(|sub| sub.start);
+    (subs, report)
+}
+
+/// Whether the text is JSON with transcript segments, an object with a `segments` or
+/// `transcription` list or a list whose first segment has a start and end.
+pub fn sniff(text: &str) -> bool {
+    let text = text.trim_start_matches('\u{feff}').trim_start();
+    // only what can be JSON is parsed
+    if !text.starts_with('{') && !text.starts_with('[') {
+        return false;
+    }
+    match serde_json::from_str::<Value>(text) {
+        Ok(Value::Array(segments)) => segments
+            .first()
+            .is_some_and(|segment| timing(segment).is_some()),
+        Ok(Value::Object(object)) => object
+            .get("segments")
+            .or_else(|| object.get("transcription"))
+            .is_some_and(Value::is_array),
+        _ => false,
+    }
+}
+
+// start and end in milliseconds, from seconds or from whisper.cpp's offsets
+fn timing(segment: &Value) -> Option<(i64, i64)> {
+    let offsets = &segment["offsets"];
+    if offsets.is_object() {
+        return Some((offsets["from"].as_i64()?, offsets["to"].as_i64()?));
+    }
+    let seconds = |key: &str| Some((segment[key].as_f64()? * 1000.0).round() as i64);
+    Some((seconds("start")?, seconds("end")?))
+}
+
+// the segment's speaker label, or the label most of its words have
+fn speaker(segment: &Value) -> Option<String> {
+    if let Some(speaker) = segment["speaker"].as_str() {
+        return Some(speaker.trim().to_string()).filter(|speaker| !speaker.is_empty());
+    }
+
+    let mut counts: Vec<(&str, usize)> = Vec::new();
+    let words = segment["words"].as_array()?;
+    for speaker in words.iter().filter_map(|word| word["speaker"].as_str()) {
+        match counts.iter_mut().find(|(known, _)| *known == speaker) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((speaker, 1)),
+        }
+    }
+    // the first one wins a tie
+    let (speaker, _) = counts
+        .iter()
+        .rev()
+        .max_by_key(|(_, count)| *count)
+        .copied()?;
+    Some(speaker.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_whisperx_segments() {
+        let (subs, report) = parse(
+            r#"{"segments": [
+                {"start": 3.0, "end": 4.25, "text": " Second", "speaker": "SPEAKER_01"},
+                {"start": 1.0, "end": 2.5, "text": " First ", "words": [
+                    {"word": "First", "speaker": "SPEAKER_00"}
+                ]},
+                {"start": 5.0, "text": "No end"},
+                {"start": 6.0, "end": 7.0, "text": "  "}
+            ]}"#,
+        );
+        let cues: Vec<(usize, i64, i64, &str, Option<&str>)> = subs
+            .iter()
+            .map(|sub| {
+                (
+                    sub.line,
+                    sub.start,
+                    sub.end,
+                    sub.text.as_str(),
+                    sub.speaker.as_deref(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            cues,
+            vec![
+                (2, 1000, 2500, "First", Some("SPEAKER_00")),
+                (1, 3000, 4250, "Second", Some("SPEAKER_01")),
+            ]
+        );
+        assert_eq!(report.errors[0].line, 3);
+        assert_eq!(report.warnings[0].line, 4);
+    }
+
+    #[test]
+    fn parses_whisper_cpp_offsets() {
+        let (subs, report) =
+            parse(r#"{"transcription": [{"offsets": {"from": 0, "to": 1500}, "text": "Hello"}]}"#);
+        assert!(report.errors.is_empty());
+        assert_eq!((subs[0].start, subs[0].end), (0, 1500));
+        assert_eq!(subs[0].speaker, None);
+    }
+
+    #[test]
+    fn takes_the_speaker_most_words_have() {
+        let (subs, _) = parse(
+            r#"[{"start": 0, "end": 1, "text": "a b c d", "words": [
+                {"speaker": "A"}, {"speaker": "B"}, {"speaker": "B"}, {"speaker": "A"}, {}
+            ]}]"#,
+        );
+        // a tie goes to the speaker heard first
+        assert_eq!(subs[0].speaker.as_deref(), Some("A"));
+    }
+
+    #[test]
+    fn reports_invalid_json() {
+        let (subs, report) = parse("{\"segments\": [\n{]");
+        assert!(subs.is_empty());
+        assert_eq!(report.errors[0].line, 2);
+
+        let (_, report) = parse("{\"text\": \"no segments\"}");
+        assert_eq!(report.errors[0].message, "file has no segments");
+    }
+
+    #[test]
+    fn sniffs_transcripts() {
+        assert!(sniff("\u{feff}{\"segments\": []}"));
+        assert!(sniff(
+            "{\"transcription\": [{\"offsets\": {\"from\": 0, \"to\": 1}}]}"
+        ));
+        assert!(sniff("[{\"start\": 1.0, \"end\": 2.0, \"text\": \"Hi\"}]"));
+        assert!(!sniff("{\"text\": \"no segments\"}"));
+        assert!(!sniff("[]"));
+        assert!(!sniff("[1, 2]"));
+        assert!(!sniff("[door slams]\n00:00:01,000 --> 00:00:02,000\nHi"));
+        assert!(!sniff("[INFORMATION]\n[TITLE]Movie"));
+        assert!(!sniff("1\n00:00:01,000 --> 00:00:02,000\n[laughs]"));
+    }
+}
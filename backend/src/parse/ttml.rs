@@ -0,0 +1,360 @@
+use std::collections::HashMap;
+
+use roxmltree::{Document, Node};
+
+use super::{ParseReport, Sub, SubKind};
+
+const TTP: &str = "http://www.w3.org/ns/ttml#parameter";
+const TTS: &str = "http://www.w3.org/ns/ttml#styling";
+const TTM: &str = "http://www.w3.org/ns/ttml#metadata";
+const XML: &str = "http://www.w3.org/XML/1998/namespace";
+
+/// Parses a decoded TTML or DFXP file into its cues.
+///
+/// Every `<p>` with timing becomes a cue, with the begin of enclosing `<div>`s added.
+/// Italic, bold, underlined and colored spans become tags, also when the style comes from
+/// a referenced `<style>`. The name of the `ttm:agent` a paragraph refers to becomes the
+/// speaker.
+pub fn parse(text: &str) -> (Vec<Sub>, ParseReport) {
+    let mut report = ParseReport::default();
+    let mut subs = Vec::new();
+
+    let document = match Document::parse(text.trim_start_matches('\u{feff}')) {
+        Ok(document) => document,
+        Err(error) => {
+            report.error(error.pos().row as usize, format!("invalid xml: {}", error));
+            return (subs, report);
+        }
+    };
+    let root = document.root_element();
+    if root.tag_name().name() != "tt" {
+        report.error(1, "file has no tt element");
+        return (subs, report);
+    }
+
+    let clock = Clock::new(root);
+    let styles: HashMap<&str, Node> = document
+        .descendants()
+        .filter(|node| node.has_tag_name("style"))
+        .filter_map(|node| Some((node.attribute((XML, "id"))?, node)))
+        .collect();
+    let agents: HashMap<&str, String> = document
+        .descendants()
+        .filter(|node| node.tag_name().name() == "agent")
+        .filter_map(|node| {
+            let name = node
+                .children()
+                .find(|child| child.tag_name().name() == "name")
+                .and_then(|name| name.text())?;
+            Some((node.attribute((XML, "id"))?, name.trim().to_string()))
+        })
+        .collect();
+
+    for paragraph in document.descendants().filter(|node| node.has_tag_name("p")) {
+        let line = document.text_pos_at(paragraph.range().start).row as usize;
+
+        let offset = paragraph
+            .ancestors()
+            .skip(1)
+            .filter_map(|ancestor| ancestor.attribute("begin"))
+            .map(|begin| clock.parse(begin))
+            .sum::<Option<i64>>();
+        let start = paragraph.attribute("begin").map(|begin| clock.parse(begin));
+        let end = match (paragraph.attribute("end"), paragraph.attribute("dur")) {
+            (Some(end), _) => Some(clock.parse(end)),
+            (None, Some(duration)) => Some(
+                clock
+                    .parse(duration)
+                    .and_then(|duration| Some(start.flatten()? + duration)),
+            ),
+            (None, None) => None,
+        };
+        let (start, end) = match (offset, start, end) {
+            (Some(offset), Some(Some(start)), Some(Some(end))) => (offset + start, offset + end),
+            (_, None, _) | (_, _, None) => {
+                report.error(line, "paragraph has no begin or end, skipped");
+                continue;
+            }
+            _ => {
+                report.error(line, "invalid time expression, paragraph skipped");
+                continue;
+            }
+        };
+        let end = if end < start {
+            report.warning(line, "cue ends before it starts, end set to start");
+            start
+        } else {
+            end
+        };
+
+        let text = tagged_text(paragraph, &styles);
+        if text.trim().is_empty() {
+            report.warning(line, "cue has no text, cue skipped");
+            continue;
+        }
+
+        let speaker = paragraph
+            .attribute((TTM, "agent"))
+            .and_then(|agent| agents.get(agent.split_whitespace().next()?))
+            .cloned();
+
+        subs.push(Sub {
+            kind: SubKind::Dialogue,
+            index: None,
+            line,
+            start,
+            end,
+            text,
+            speaker,
+            style: paragraph.attribute("style").map(str::to_string),
+            markup: Vec::new(),
+        });
+    }
+
+    if subs.is_empty() && report.errors.is_empty() {
+        report.error(1, "no cues found");
+    }
+    subs.sort_by_key(|sub| sub.start);
+    (subs, report)
+}
+
+/// Whether the text looks like a TTML document.
+pub fn sniff(text: &str) -> bool {
+    let text = text.trim_start_matches('\u{feff}').trim_start();
+    text.starts_with('<')
+        && (text.contains("<tt ") || text.contains("<tt>") || text.contains(":tt "))
+}
+
+// the rates time expressions in frames and ticks are relative to
+struct Clock {
+    framerate: f64,
+    tick_rate: f64,
+}
+
+impl Clock {
+    fn new(root: Node) -> Clock {
+        let parameter = |name: &str| root.attribute((TTP, name));
+        let framerate = parameter("frameRate")
+            .and_then(|rate| rate.trim().parse::<f64>().ok())
+            .unwrap_or(30.0);
+        // like "1000 1001" for NTSC rates
+        let multiplier = parameter("frameRateMultiplier")
+            .and_then(|multiplier| {
+                let (numerator, denominator) = multiplier.trim().split_once(' ')?;
+                Some(numerator.parse::<f64>().ok()? / denominator.trim().parse::<f64>().ok()?)
+            })
+            .unwrap_or(1.0);
+        let tick_rate = parameter("tickRate")
+            .and_then(|rate| rate.trim().parse::<f64>().ok())
+            .unwrap_or(1.0);
+        Clock {
+            framerate: framerate * multiplier,
+            tick_rate,
+        }
+    }
+
+    // parses clock times like "00:01:02.500" or "00:01:02:12" and offsets like "62.5s",
+    // "1500ms", "30f" or "10000000t" into milliseconds
+    fn parse(&self, expression: &str) -> Option<i64> {
+        let expression = expression.trim();
+        if expression.contains(':') {
+            let parts: Vec<&str> = expression.split(':').collect();
+            let (hours, minutes, seconds, frames) = match parts[..] {
+                [hours, minutes, seconds] => (hours, minutes, seconds, None),
+                [hours, minutes, seconds, frames] => (hours, minutes, seconds, Some(frames)),
+                _ => return None,
+            };
+            let seconds = hours.parse::<f64>().ok()? * 3600.0
+                + minutes.parse::<f64>().ok()? * 60.0
+                + seconds.parse::<f64>().ok()?;
+            let frames = match frames {
+                Some(frames) => frames.parse::<f64>().ok()? / self.framerate,
+                None => 0.0,
+            };
+            return Some(((seconds + frames) * 1000.0).round() as i64);
+        }
+
+        let split = expression.find(|c: char| c.is_ascii_alphabetic())?;
+        let (value, metric) = expression.split_at(split);
+        let value = value.parse::<f64>().ok()?;
+        let seconds = match metric {
+            "h" => value * 3600.0,
+            "m" => value * 60.0,
+            "s" => value,
+            "ms" => value / 1000.0,
+            "f" => value / self.framerate,
+            "t" => value / self.tick_rate,
+            _ => return None,
+        };
+        Some((seconds * 1000.0).round() as i64)
+    }
+}
+
+// the text of a paragraph, with <br/> as line breaks and styling as tags
+fn tagged_text(paragraph: Node, styles: &HashMap<&str, Node>) -> String {
+    let tags = style_tags(paragraph, styles);
+    let mut text = String::new();
+    for (open, _) in &tags {
+        text.push_str(open);
+    }
+    push_node(paragraph, styles, &mut text);
+    for (_, close) in tags.iter().rev() {
+        text.push_str(close);
+    }
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn push_node(node: Node, styles: &HashMap<&str, Node>, text: &mut String) {
+    for child in node.children() {
+        if child.is_text() {
+            // xml whitespace, line breaks only come from <br/>
+            let content = child.text().unwrap_or_default();
+            let collapsed = content.split_whitespace().collect::<Vec<_>>().join(" ");
+            if content.starts_with(char::is_whitespace) && !text.ends_with([' ', '\n']) {
+                text.push(' ');
+            }
+            text.push_str(&collapsed);
+            if content.ends_with(char::is_whitespace) && !collapsed.is_empty() {
+                text.push(' ');
+            }
+        } else if child.has_tag_name("br") {
+            text.push('\n');
+        } else if child.has_tag_name("span") {
+            let tags = style_tags(child, styles);
+            for (open, _) in &tags {
+                text.push_str(open);
+            }
+            push_node(child, styles, text);
+            for (_, close) in tags.iter().rev() {
+                text.push_str(close);
+            }
+        }
+    }
+}
+
+// the tags for the styling of a paragraph or span, set on the element itself or on the
+// styles it refers to
+fn style_tags(element: Node, styles: &HashMap<&str, Node>) -> Vec<(String, &'static str)> {
+    let referenced = element
+        .attribute("style")
+        .unwrap_or_default()
+        .split_whitespace()
+        .filter_map(|id| styles.get(id).copied());
+    // attributes on the element win over the ones of the styles it refers to
+    let style = |name: &str| {
+        element.attribute((TTS, name)).or_else(|| {
+            referenced
+                .clone()
+                .find_map(|style| style.attribute((TTS, name)))
+        })
+    };
+
+    let mut tags = Vec::new();
+    if style("fontStyle") == Some("italic") {
+        tags.push(("<i>".to_string(), "</i>"));
+    }
+    if style("fontWeight") == Some("bold") {
+        tags.push(("<b>".to_string(), "</b>"));
+    }
+    if style("textDecoration").is_some_and(|decoration| decoration.contains("underline")) {
+        tags.push(("<u>".to_string(), "</u>"));
+    }
+    if let Some(color) = style("color") {
+        let color: String = color.split_whitespace().collect();
+        tags.push((format!("<font color=\"{}\">", color), "</font>"));
+    }
+    tags
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DOCUMENT: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<tt xmlns="http://www.w3.org/ns/ttml" xmlns:tts="http://www.w3.org/ns/ttml#styling"
+    xmlns:ttm="http://www.w3.org/ns/ttml#metadata" xmlns:ttp="http://www.w3.org/ns/ttml#parameter"
+    ttp:frameRate="25" ttp:tickRate="10000000">
+  <head>
+    <styling>
+      <style xml:id="emphasis" tts:fontStyle="italic"/>
+    </styling>
+    <metadata>
+      <ttm:agent xml:id="john"><ttm:name>Sir John</ttm:name></ttm:agent>
+    </metadata>
+  </head>
+  <body>
+    <div begin="10s">
+      <p begin="00:00:01.000" end="00:00:02:12" ttm:agent="john">Where
+        <span style="emphasis">were</span> you?<br/><span tts:color="red" tts:fontWeight="bold">Answer</span></p>
+      <p begin="0.5s" dur="1500ms" style="emphasis">At home.</p>
+    </div>
+    <p begin="20000000t" end="1m">Late</p>
+    <p begin="5s">No end</p>
+    <p begin="later" end="1s">Bad time</p>
+  </body>
+</tt>"#;
+
+    #[test]
+    fn parses_paragraphs() {
+        let (subs, report) = parse(DOCUMENT);
+        let cues: Vec<(i64, i64, &str, Option<&str>)> = subs
+            .iter()
+            .map(|sub| {
+                (
+                    sub.start,
+                    sub.end,
+                    sub.text.as_str(),
+                    sub.speaker.as_deref(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            cues,
+            vec![
+                (2000, 60000, "Late", None),
+                (10500, 12000, "<i>At home.</i>", None),
+                (
+                    11000,
+                    12480,
+                    "Where <i>were</i> you?\n<b><font color=\"red\">Answer</font></b>",
+                    Some("Sir John")
+                ),
+            ]
+        );
+        assert_eq!(subs[1].style.as_deref(), Some("emphasis"));
+
+        let errors: Vec<(usize, &str)> = report
+            .errors
+            .iter()
+            .map(|issue| (issue.line, issue.message.as_str()))
+            .collect();
+        assert_eq!(
+            errors,
+            vec![
+                (20, "paragraph has no begin or end, skipped"),
+                (21, "invalid time expression, paragraph skipped"),
+            ]
+        );
+    }
+
+    #[test]
+    fn reports_invalid_documents() {
+        let (subs, report) = parse("<tt><p>unclosed</tt>");
+        assert!(subs.is_empty());
+        assert!(report.errors[0].message.starts_with("invalid xml"));
+
+        let (_, report) = parse("<html/>");
+        assert_eq!(report.errors[0].message, "file has no tt element");
+    }
+
+    #[test]
+    fn sniffs_documents() {
+        assert!(sniff(DOCUMENT));
+        assert!(sniff("<tt:tt xmlns:tt=\"http://www.w3.org/ns/ttml\">"));
+        assert!(!sniff("WEBVTT"));
+    }
+}
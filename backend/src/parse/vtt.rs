@@ -0,0 +1,219 @@
+use super::{parse_timestamp, ParseReport, Sub, SubKind};
+
+/// Parses a decoded WebVTT file into its cues.
+///
+/// `NOTE`, `STYLE` and `REGION` blocks are skipped, cue settings are ignored and the
+/// speaker of the first `<v Speaker>` voice span is kept on the cue. Of the other tags
+/// only `<i>`, `<b>` and `<u>` stay in the text.
+pub fn parse(text: &str) -> (Vec<Sub>, ParseReport) {
+    let mut report = ParseReport::default();
+
+    let text = text.trim_start_matches('\u{feff}');
+    let text = text.replace("\r\n", "\n").replace('\r', "\n");
+    let lines: Vec<&str> = text.split('\n').map(str::trim_end).collect();
+
+    if !lines.first().is_some_and(|line| line.starts_with("WEBVTT")) {
+        report.warning(1, "file does not start with WEBVTT");
+    }
+
+    let mut subs = Vec::new();
+    for (first, block) in blocks(&lines) {
+        let kind = block[0].split_whitespace().next().unwrap_or_default();
+        if first == 0 && kind == "WEBVTT" || matches!(kind, "NOTE" | "STYLE" | "REGION") {
+            continue;
+        }
+
+        let timing = match block.iter().position(|line| line.contains("-->")) {
+            Some(timing @ (0 | 1)) => timing,
+            Some(_) | None => {
+                report.error(first + 1, "block is neither a cue nor a note, skipped");
+                continue;
+            }
+        };
+        let line = first + timing + 1;
+
+        let (start, end) = match parse_timing(block[timing]) {
+            Some(times) => times,
+            None => {
+                report.error(
+                    line,
+                    format!("invalid timing '{}', cue skipped", block[timing]),
+                );
+                continue;
+            }
+        };
+
+        let end = if end < start {
+            report.warning(line, "cue ends before it starts, end set to start");
+            start
+        } else {
+            end
+        };
+
+        let payload = &block[timing + 1..];
+        let mut speakers = payload.iter().flat_map(|line| voices(line));
+        let speaker = speakers.next();
+        if speakers.any(|other| Some(&other) != speaker.as_ref()) {
+            report.warning(line, "cue has more than one voice, only the first is kept");
+        }
+
+        let text = payload
+            .iter()
+            .map(|line| strip_tags(line))
+            .filter(|line| !line.is_empty())
+            .collect::<Vec<_>>()
+            .join("\n");
+        if text.is_empty() {
+            report.warning(line, "cue has no text, cue skipped");
+            continue;
+        }
+
+        subs.push(Sub {
+            kind: SubKind::Dialogue,
+            index: None,
+            line,
+            start,
+            end,
+            text,
+            speaker,
+            style: None,
+            markup: Vec::new(),
+        });
+    }
+
+    (subs, report)
+}
+
+// splits the lines into blocks separated by blank lines, with the index of their first line
+fn blocks<'a>(lines: &'a [&'a str]) -> impl Iterator<Item = (usize, &'a [&'a str])> {
+    let mut start = 0;
+    std::iter::from_fn(move || {
+        while start < lines.len() && lines[start].trim().is_empty() {
+            start += 1;
+        }
+        if start >= lines.len() {
+            return None;
+        }
+        let end = lines[start..]
+            .iter()
+            .position(|line| line.trim().is_empty())
+            .map_or(lines.len(), |len| start + len);
+        let block = (start, &lines[start..end]);
+        start = end;
+        Some(block)
+    })
+}
+
+// parses "00:01.000 --> 00:04.000 align:start position:10%" and ignores the cue settings
+fn parse_timing(line: &str) -> Option<(i64, i64)> {
+    let (start, end) = line.split_once("-->")?;
+    let end = end.split_whitespace().next()?;
+    Some((parse_timestamp(start.trim())?, parse_timestamp(end)?))
+}
+
+// the speakers of all voice spans in a line, <v Speaker> or with classes <v.loud Speaker>
+fn voices(line: &str) -> Vec<String> {
+    line.match_indices("<v")
+        .filter_map(|(i, _)| {
+            let tag = &line[i + 2..];
+            let tag = &tag[..tag.find('>')?];
+            let name = match tag.strip_prefix('.') {
+                Some(classes) => classes.split_once(char::is_whitespace)?.1,
+                None if tag.starts_with(char::is_whitespace) => tag,
+                None => return None,
+            };
+            let name = unescape(name.trim());
+            (!name.is_empty()).then_some(name)
+        })
+        .collect()
+}
+
+// removes all tags but <i>, <b> and <u>, like <c.yellow>, <v Speaker> and <00:00:01.000>,
+// and unescapes entities
+fn strip_tags(line: &str) -> String {
+    let mut text = String::with_capacity(line.len());
+    let mut rest = line;
+    while let Some(open) = rest.find('<') {
+        text.push_str(&rest[..open]);
+        let close = match rest[open..].find('>') {
+            Some(close) => open + close + 1,
+            None => rest.len(),
+        };
+        let tag = rest[open..close].trim_start_matches(['<', '/']);
+        if tag.starts_with(['i', 'b', 'u']) && tag[1..].starts_with(['>', '.']) {
+            text.push_str(&rest[open..close]);
+        }
+        rest = &rest[close..];
+    }
+    text.push_str(rest);
+    unescape(text.trim())
+}
+
+fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&nbsp;", " ")
+        .replace("&lrm;", "\u{200e}")
+        .replace("&rlm;", "\u{200f}")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_cues_and_voices() {
+        let (subs, report) = parse(
+            "WEBVTT - a title\n\
+            \n\
+            NOTE this is skipped\n\
+            \n\
+            STYLE\n\
+            ::cue { color: yellow }\n\
+            \n\
+            intro\n\
+            00:01.000 --> 00:04.000 align:start position:10%\n\
+            <v.loud Sir John>Where <i>were</i> you?</v>\n\
+            \n\
+            01:00:00.000 --> 01:00:01.500\n\
+            <c.yellow>Fish &amp; chips</c> <00:00:01.000>now\n",
+        );
+        assert!(report.warnings.is_empty() && report.errors.is_empty());
+        assert_eq!(subs.len(), 2);
+        assert_eq!((subs[0].start, subs[0].end, subs[0].line), (1000, 4000, 9));
+        assert_eq!(subs[0].speaker.as_deref(), Some("Sir John"));
+        assert_eq!(subs[0].text, "Where <i>were</i> you?");
+        assert_eq!((subs[1].start, subs[1].end), (3_600_000, 3_601_500));
+        assert_eq!(subs[1].speaker, None);
+        assert_eq!(subs[1].text, "Fish & chips now");
+    }
+
+    #[test]
+    fn reports_broken_blocks() {
+        let (subs, report) = parse(
+            "00:01.000 --> 00:02.000\n\
+            <v A>One</v> <v B>Two</v>\n\
+            \n\
+            some\n\
+            stray\n\
+            text\n\
+            \n\
+            00:05.000 --> 00:03.000\n\
+            Backwards\n\
+            \n\
+            00:06.000 --> soon\n\
+            Skipped\n",
+        );
+        assert_eq!(subs.len(), 2);
+        assert_eq!(subs[0].speaker.as_deref(), Some("A"));
+        assert_eq!((subs[1].start, subs[1].end), (5000, 5000));
+
+        let warnings: Vec<usize> = report.warnings.iter().map(|issue| issue.line).collect();
+        let errors: Vec<usize> = report.errors.iter().map(|issue| issue.line).collect();
+        // no header, two voices, backwards timing
+        assert_eq!(warnings, vec![1, 1, 8]);
+        // stray block, invalid timing
+        assert_eq!(errors, vec![4, 11]);
+    }
+}